@@ -0,0 +1,185 @@
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use log::{debug, error};
+use serde::Deserialize;
+
+mod uinput;
+
+use uinput::UinputKeyboard;
+
+/// How keys of the on screen keyboard are sent.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyboardBackendName {
+    /// X11/XWayland clients only
+    #[default]
+    Enigo,
+    /// virtual keyboard device, works on wayland, in gamescope and on the console,
+    /// needs write access to /dev/uinput
+    Uinput,
+}
+
+#[derive(Deserialize)]
+pub struct KeyboardConfig {
+    pub keyboard_backend: Option<KeyboardBackendName>,
+}
+
+/// Sends keys and keeps track of held keys, so they can be released on exit.
+pub trait KeyboardBackend: Send {
+    /// Presses or releases a key by name, e.g. `shift`, or a single character.
+    fn key(&mut self, key: &str, direction: Direction) -> Result<(), String>;
+    /// Releases all keys held by this backend.
+    fn release_all(&mut self);
+}
+
+/// Creates the backend, falls back to enigo if uinput isn't available
+/// and to sending no keys if enigo isn't available either.
+pub fn create_keyboard_backend(name: KeyboardBackendName) -> Box<dyn KeyboardBackend> {
+    match name {
+        KeyboardBackendName::Uinput => match UinputKeyboard::create() {
+            Ok(keyboard) => return Box::new(keyboard),
+            Err(e) => error!("Failed to create uinput keyboard, using enigo: {}", e),
+        },
+        KeyboardBackendName::Enigo => {}
+    }
+    return match EnigoKeyboard::new() {
+        Ok(keyboard) => Box::new(keyboard),
+        Err(e) => {
+            error!("Failed to create enigo keyboard, keys won't be sent: {}", e);
+            Box::new(NoKeyboard)
+        }
+    };
+}
+
+static KEY_MAP: &[(&str, Key)] = &[
+    ("shift", Key::Shift),
+    ("left_shift", Key::LShift),
+    ("right_shift", Key::RShift),
+    ("control", Key::Control),
+    ("lcontrol", Key::LControl),
+    ("rcontrol", Key::RControl),
+    ("alt", Key::Alt),
+    ("meta", Key::Meta),
+    ("backspace", Key::Backspace),
+    ("return", Key::Return),
+    ("delete", Key::Delete),
+    ("home", Key::Home),
+    ("page_up", Key::PageUp),
+    ("page_down", Key::PageDown),
+    ("up_arrow", Key::UpArrow),
+    ("down_arrow", Key::DownArrow),
+    ("left_arrow", Key::LeftArrow),
+    ("right_arrow", Key::RightArrow),
+    ("escape", Key::Escape),
+    ("space", Key::Space),
+    ("tab", Key::Tab),
+    ("f1", Key::F1),
+    ("f2", Key::F2),
+    ("f3", Key::F3),
+    ("f4", Key::F4),
+    ("f5", Key::F5),
+    ("f6", Key::F6),
+    ("f7", Key::F7),
+    ("f8", Key::F8),
+    ("f9", Key::F9),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+    ("f13", Key::F13),
+    ("f14", Key::F14),
+    ("f15", Key::F15),
+    ("f16", Key::F16),
+    ("f17", Key::F17),
+    ("f18", Key::F18),
+    ("f19", Key::F19),
+    ("f20", Key::F20),
+    ("f21", Key::F21),
+    ("f22", Key::F22),
+    ("f23", Key::F23),
+    ("f24", Key::F24),
+    ("f25", Key::F25),
+    ("f26", Key::F26),
+    ("f27", Key::F27),
+    ("f28", Key::F28),
+    ("f29", Key::F29),
+    ("f30", Key::F30),
+    ("f31", Key::F31),
+    ("f32", Key::F32),
+    ("f33", Key::F33),
+    ("f34", Key::F34),
+    ("f35", Key::F35),
+];
+
+fn map_key(key: &str) -> Option<Key> {
+    if key.len() == 1 {
+        return Some(Key::Unicode(key.chars().next().unwrap()));
+    }
+    let key_lowercase = key.to_lowercase();
+    for mapping in KEY_MAP {
+        if mapping.0 == key_lowercase {
+            return Some(mapping.1);
+        }
+    }
+    None
+}
+
+/// Sends keys with enigo, X11/XWayland only.
+struct EnigoKeyboard {
+    enigo: Enigo,
+    held: Vec<Key>,
+}
+
+impl EnigoKeyboard {
+    fn new() -> Result<Self, String> {
+        let enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+        return Ok(Self {
+            enigo: enigo,
+            held: Vec::new(),
+        });
+    }
+}
+
+impl KeyboardBackend for EnigoKeyboard {
+    fn key(&mut self, key: &str, direction: Direction) -> Result<(), String> {
+        let mapped_key = match map_key(key) {
+            Some(mapped_key) => mapped_key,
+            None => return Err(format!("unknown key {}", key)),
+        };
+        match direction {
+            Direction::Press => {
+                if !self.held.contains(&mapped_key) {
+                    self.held.push(mapped_key);
+                }
+            }
+            Direction::Release => self.held.retain(|held_key| *held_key != mapped_key),
+            Direction::Click => {}
+        }
+        return self
+            .enigo
+            .key(mapped_key, direction)
+            .map_err(|e| e.to_string());
+    }
+
+    fn release_all(&mut self) {
+        debug!("Releasing {} held keys", self.held.len());
+        // modifiers are released regardless, a stuck modifier breaks all input
+        let mut keys: Vec<Key> = self.held.drain(..).collect();
+        keys.extend([Key::Shift, Key::Alt, Key::Control, Key::Meta]);
+        for key in keys {
+            match self.enigo.key(key, Direction::Release) {
+                Ok(_) => {}
+                Err(e) => error!("Could not release held key: {}", e),
+            }
+        }
+    }
+}
+
+/// Used if no backend could be created, e.g. enigo without X11.
+struct NoKeyboard;
+
+impl KeyboardBackend for NoKeyboard {
+    fn key(&mut self, key: &str, _direction: Direction) -> Result<(), String> {
+        return Err(format!("no keyboard backend to send {}", key));
+    }
+
+    fn release_all(&mut self) {}
+}
@@ -0,0 +1,312 @@
+use enigo::Direction;
+use log::{debug, error};
+use std::{
+    ffi::c_char,
+    fs::{File, OpenOptions},
+    io, mem,
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+};
+
+use super::KeyboardBackend;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0x00;
+const BUS_VIRTUAL: u16 = 0x06;
+/// no vendor, so steam, SDL and the plugins don't take it for a controller
+const VIRTUAL_VENDOR: u16 = 0x0000;
+const VIRTUAL_PRODUCT: u16 = 0x0001;
+
+// see linux/input-event-codes.h
+const KEY_ESC: u16 = 1;
+const KEY_MINUS: u16 = 12;
+const KEY_EQUAL: u16 = 13;
+const KEY_BACKSPACE: u16 = 14;
+const KEY_TAB: u16 = 15;
+const KEY_LEFTBRACE: u16 = 26;
+const KEY_RIGHTBRACE: u16 = 27;
+const KEY_ENTER: u16 = 28;
+const KEY_LEFTCTRL: u16 = 29;
+const KEY_SEMICOLON: u16 = 39;
+const KEY_APOSTROPHE: u16 = 40;
+const KEY_GRAVE: u16 = 41;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_BACKSLASH: u16 = 43;
+const KEY_COMMA: u16 = 51;
+const KEY_DOT: u16 = 52;
+const KEY_SLASH: u16 = 53;
+const KEY_RIGHTSHIFT: u16 = 54;
+const KEY_LEFTALT: u16 = 56;
+const KEY_SPACE: u16 = 57;
+const KEY_F1: u16 = 59;
+const KEY_F11: u16 = 87;
+const KEY_F13: u16 = 183;
+const KEY_RIGHTCTRL: u16 = 97;
+const KEY_HOME: u16 = 102;
+const KEY_UP: u16 = 103;
+const KEY_PAGEUP: u16 = 104;
+const KEY_LEFT: u16 = 105;
+const KEY_RIGHT: u16 = 106;
+const KEY_DOWN: u16 = 108;
+const KEY_PAGEDOWN: u16 = 109;
+const KEY_DELETE: u16 = 111;
+const KEY_LEFTMETA: u16 = 125;
+/// highest key code enabled on the device
+const KEY_MAX_USED: u16 = 194;
+
+const IOC_WRITE: u32 = 1;
+
+/// _IOC for the uinput ioctl type 'U', see linux/uinput.h
+const fn uiioc(dir: u32, nr: u32, size: usize) -> libc::c_ulong {
+    return (dir << 30 | (size as u32) << 16 | (b'U' as u32) << 8 | nr) as libc::c_ulong;
+}
+
+const UI_DEV_CREATE: libc::c_ulong = uiioc(0, 1, 0);
+const UI_DEV_DESTROY: libc::c_ulong = uiioc(0, 2, 0);
+const UI_DEV_SETUP: libc::c_ulong = uiioc(IOC_WRITE, 3, mem::size_of::<libc::uinput_setup>());
+const UI_SET_EVBIT: libc::c_ulong = uiioc(IOC_WRITE, 100, mem::size_of::<libc::c_int>());
+const UI_SET_KEYBIT: libc::c_ulong = uiioc(IOC_WRITE, 101, mem::size_of::<libc::c_int>());
+
+static NAMED_KEYS: &[(&str, u16)] = &[
+    ("shift", KEY_LEFTSHIFT),
+    ("left_shift", KEY_LEFTSHIFT),
+    ("right_shift", KEY_RIGHTSHIFT),
+    ("control", KEY_LEFTCTRL),
+    ("lcontrol", KEY_LEFTCTRL),
+    ("rcontrol", KEY_RIGHTCTRL),
+    ("alt", KEY_LEFTALT),
+    ("meta", KEY_LEFTMETA),
+    ("backspace", KEY_BACKSPACE),
+    ("return", KEY_ENTER),
+    ("delete", KEY_DELETE),
+    ("home", KEY_HOME),
+    ("page_up", KEY_PAGEUP),
+    ("page_down", KEY_PAGEDOWN),
+    ("up_arrow", KEY_UP),
+    ("down_arrow", KEY_DOWN),
+    ("left_arrow", KEY_LEFT),
+    ("right_arrow", KEY_RIGHT),
+    ("escape", KEY_ESC),
+    ("space", KEY_SPACE),
+    ("tab", KEY_TAB),
+];
+
+/// Key code of characters on the us layout, with shift or without.
+static CHAR_KEYS: &[(&str, &str, u16)] = &[
+    ("1234567890", "!@#$%^&*()", 2),
+    ("qwertyuiop", "QWERTYUIOP", 16),
+    ("asdfghjkl", "ASDFGHJKL", 30),
+    ("zxcvbnm", "ZXCVBNM", 44),
+    ("-", "_", KEY_MINUS),
+    ("=", "+", KEY_EQUAL),
+    ("[", "{", KEY_LEFTBRACE),
+    ("]", "}", KEY_RIGHTBRACE),
+    (";", ":", KEY_SEMICOLON),
+    ("'", "\"", KEY_APOSTROPHE),
+    ("`", "~", KEY_GRAVE),
+    ("\\", "|", KEY_BACKSLASH),
+    (",", "<", KEY_COMMA),
+    (".", ">", KEY_DOT),
+    ("/", "?", KEY_SLASH),
+    (" ", " ", KEY_SPACE),
+    ("\t", "\t", KEY_TAB),
+    ("\n", "\n", KEY_ENTER),
+];
+
+/// Key code by name or single character, and whether shift is needed.
+/// Characters are mapped for the us layout, the compositor applies the active layout.
+fn map_key(key: &str) -> Option<(u16, bool)> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => {
+            for (plain, shifted, first_code) in CHAR_KEYS {
+                match plain.chars().position(|plain_c| plain_c == c) {
+                    Some(i) => return Some((first_code + i as u16, false)),
+                    None => {}
+                }
+                match shifted.chars().position(|shifted_c| shifted_c == c) {
+                    Some(i) => return Some((first_code + i as u16, true)),
+                    None => {}
+                }
+            }
+            return None;
+        }
+        _ => {}
+    }
+    let key_lowercase = key.to_lowercase();
+    for (name, code) in NAMED_KEYS {
+        if *name == key_lowercase {
+            return Some((*code, false));
+        }
+    }
+    let function_key = key_lowercase
+        .strip_prefix('f')
+        .and_then(|number| number.parse::<u16>().ok());
+    return match function_key {
+        Some(number @ 1..=10) => Some((KEY_F1 + number - 1, false)),
+        Some(number @ 11..=12) => Some((KEY_F11 + number - 11, false)),
+        Some(number @ 13..=24) => Some((KEY_F13 + number - 13, false)),
+        _ => None,
+    };
+}
+
+/// Virtual keyboard created with /dev/uinput, destroyed when dropped.
+pub struct UinputKeyboard {
+    file: File,
+    /// keys pressed by name or character
+    held: Vec<u16>,
+    /// keys pressed with shift pressed for them, e.g. uppercase characters
+    shifted: Vec<u16>,
+}
+
+impl UinputKeyboard {
+    pub fn create() -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+        ioctl(fd, UI_SET_EVBIT, EV_KEY as libc::c_int)?;
+        for code in 1..=KEY_MAX_USED {
+            ioctl(fd, UI_SET_KEYBIT, code as libc::c_int)?;
+        }
+        let mut setup: libc::uinput_setup = unsafe { mem::zeroed() };
+        setup.id.bustype = BUS_VIRTUAL;
+        setup.id.vendor = VIRTUAL_VENDOR;
+        setup.id.product = VIRTUAL_PRODUCT;
+        for (i, c) in "steamdeck-keyboard".bytes().enumerate() {
+            setup.name[i] = c as c_char;
+        }
+        ioctl(fd, UI_DEV_SETUP, &setup as *const libc::uinput_setup)?;
+        ioctl(fd, UI_DEV_CREATE, 0)?;
+        debug!("Created uinput keyboard");
+        return Ok(Self {
+            file: file,
+            held: Vec::new(),
+            shifted: Vec::new(),
+        });
+    }
+
+    fn write_key(&self, code: u16, pressed: bool) -> io::Result<()> {
+        let events = [
+            input_event(EV_KEY, code, pressed as i32),
+            input_event(EV_SYN, SYN_REPORT, 0),
+        ];
+        let res = unsafe {
+            libc::write(
+                self.file.as_raw_fd(),
+                events.as_ptr() as *const libc::c_void,
+                mem::size_of_val(&events),
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    fn press(&mut self, code: u16, shift: bool) -> io::Result<()> {
+        // shift held by the user already applies
+        let add_shift = shift && !self.held.contains(&KEY_LEFTSHIFT);
+        if add_shift {
+            self.write_key(KEY_LEFTSHIFT, true)?;
+            self.shifted.push(code);
+        }
+        self.write_key(code, true)?;
+        if !self.held.contains(&code) {
+            self.held.push(code);
+        }
+        return Ok(());
+    }
+
+    fn release(&mut self, code: u16) -> io::Result<()> {
+        self.held.retain(|held_code| *held_code != code);
+        self.write_key(code, false)?;
+        if self.shifted.contains(&code) {
+            self.shifted.retain(|shifted_code| *shifted_code != code);
+            if self.shifted.is_empty() && !self.held.contains(&KEY_LEFTSHIFT) {
+                self.write_key(KEY_LEFTSHIFT, false)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl KeyboardBackend for UinputKeyboard {
+    fn key(&mut self, key: &str, direction: Direction) -> Result<(), String> {
+        let (code, shift) = match map_key(key) {
+            Some(mapped) => mapped,
+            None => return Err(format!("unknown key {}", key)),
+        };
+        let result = match direction {
+            Direction::Press => self.press(code, shift),
+            Direction::Release => self.release(code),
+            Direction::Click => self.press(code, shift).and_then(|_| self.release(code)),
+        };
+        return result.map_err(|e| e.to_string());
+    }
+
+    fn release_all(&mut self) {
+        debug!("Releasing {} held keys", self.held.len());
+        let mut codes: Vec<u16> = self.held.drain(..).collect();
+        if !self.shifted.is_empty() {
+            self.shifted.clear();
+            codes.push(KEY_LEFTSHIFT);
+        }
+        for code in codes {
+            match self.write_key(code, false) {
+                Ok(_) => {}
+                Err(e) => error!("Could not release held key: {}", e),
+            }
+        }
+    }
+}
+
+impl Drop for UinputKeyboard {
+    fn drop(&mut self) {
+        self.release_all();
+        let _ = ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY, 0);
+    }
+}
+
+fn input_event(type_: u16, code: u16, value: i32) -> libc::input_event {
+    let mut event: libc::input_event = unsafe { mem::zeroed() };
+    event.type_ = type_;
+    event.code = code;
+    event.value = value;
+    return event;
+}
+
+fn ioctl<T>(fd: libc::c_int, request: libc::c_ulong, arg: T) -> io::Result<()> {
+    let res = unsafe { libc::ioctl(fd, request as _, arg) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_characters_with_shift() {
+        assert_eq!(map_key("a"), Some((30, false)));
+        assert_eq!(map_key("A"), Some((30, true)));
+        assert_eq!(map_key("0"), Some((11, false)));
+        assert_eq!(map_key(")"), Some((11, true)));
+        assert_eq!(map_key("?"), Some((KEY_SLASH, true)));
+        assert_eq!(map_key("ä"), None);
+    }
+
+    #[test]
+    fn maps_named_keys() {
+        assert_eq!(map_key("Shift"), Some((KEY_LEFTSHIFT, false)));
+        assert_eq!(map_key("return"), Some((KEY_ENTER, false)));
+        assert_eq!(map_key("f1"), Some((KEY_F1, false)));
+        assert_eq!(map_key("f12"), Some((88, false)));
+        assert_eq!(map_key("f24"), Some((194, false)));
+        assert_eq!(map_key("f25"), None);
+        assert_eq!(map_key("unknown"), None);
+    }
+}
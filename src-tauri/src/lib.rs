@@ -1,99 +1,56 @@
 extern crate hidapi;
 
 use std::str::FromStr;
-use std::sync::mpsc::{self, Sender};
+use std::sync::mpsc;
+use std::time::Duration;
 use std::{fs, sync::Mutex};
 
 use gtk::{gdk::WindowTypeHint, prelude::GtkWindowExt};
-use log::{debug, error, log, warn, Level};
+use log::{debug, error, info, log, warn, Level};
+use serde::{Deserialize, Serialize};
 
-use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use enigo::Direction;
 use signal_hook::consts::*;
 use signal_hook::iterator::Signals;
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::TrayIconBuilder;
 use tauri::{Emitter, Manager, State};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
+mod keyboard;
 mod plugin;
 
+use keyboard::{create_keyboard_backend, KeyboardBackend, KeyboardConfig};
+use plugin::{
+    HapticCommand, HapticPulse, HapticStep, Plugin, PluginCommand, PluginEvent, Rumble,
+    SuspendRule, SuspendRules,
+};
+
 struct AppState {
-    enigo: Enigo,
-    pause_tx: Sender<bool>,
-    config_tx: Sender<String>,
-    trigger_haptic_tx: Sender<u8>,
+    keyboard: Box<dyn KeyboardBackend>,
+    /// command senders of running plugins
+    plugins: Vec<UnboundedSender<PluginCommand>>,
+    /// processes suspended depending on keyboard visibility
+    suspend_rules: SuspendRules,
 }
 
-static KEY_MAP: &[(&str, Key)] = &[
-    ("shift", Key::Shift),
-    ("left_shift", Key::LShift),
-    ("right_shift", Key::RShift),
-    ("control", Key::Control),
-    ("lcontrol", Key::LControl),
-    ("rcontrol", Key::RControl),
-    ("alt", Key::Alt),
-    ("meta", Key::Meta),
-    ("backspace", Key::Backspace),
-    ("return", Key::Return),
-    ("delete", Key::Delete),
-    ("home", Key::Home),
-    ("page_up", Key::PageUp),
-    ("page_down", Key::PageDown),
-    ("up_arrow", Key::UpArrow),
-    ("down_arrow", Key::DownArrow),
-    ("left_arrow", Key::LeftArrow),
-    ("right_arrow", Key::RightArrow),
-    ("escape", Key::Escape),
-    ("space", Key::Space),
-    ("tab", Key::Tab),
-    ("f1", Key::F1),
-    ("f2", Key::F2),
-    ("f3", Key::F3),
-    ("f4", Key::F4),
-    ("f5", Key::F5),
-    ("f6", Key::F6),
-    ("f7", Key::F7),
-    ("f8", Key::F8),
-    ("f9", Key::F9),
-    ("f10", Key::F10),
-    ("f11", Key::F11),
-    ("f12", Key::F12),
-    ("f13", Key::F13),
-    ("f14", Key::F14),
-    ("f15", Key::F15),
-    ("f16", Key::F16),
-    ("f17", Key::F17),
-    ("f18", Key::F18),
-    ("f19", Key::F19),
-    ("f20", Key::F20),
-    ("f21", Key::F21),
-    ("f22", Key::F22),
-    ("f23", Key::F23),
-    ("f24", Key::F24),
-    ("f25", Key::F25),
-    ("f26", Key::F26),
-    ("f27", Key::F27),
-    ("f28", Key::F28),
-    ("f29", Key::F29),
-    ("f30", Key::F30),
-    ("f31", Key::F31),
-    ("f32", Key::F32),
-    ("f33", Key::F33),
-    ("f34", Key::F34),
-    ("f35", Key::F35),
-];
+/// Payload of `device-connected` and `device-disconnected`.
+#[derive(Clone, Serialize)]
+struct DeviceEvent {
+    /// name of the plugin
+    source: String,
+    /// name of the device
+    name: String,
+}
 
-fn map_key(key: &str) -> Option<Key> {
-    if key.len() == 1 {
-        return Some(Key::Unicode(key.chars().next().unwrap()));
-    }
-    let key_lowercase = key.to_lowercase();
-    for mapping in KEY_MAP {
-        if mapping.0 == key_lowercase {
-            return Some(mapping.1);
-        }
-    }
-    error!("unknown key {}", key);
-    None
+#[derive(Deserialize)]
+struct PluginsConfig {
+    plugins: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct SuspendRulesConfig {
+    suspend_rules: Option<Vec<SuspendRule>>,
 }
 
 fn map_state(state: &str) -> Option<Direction> {
@@ -107,45 +64,96 @@ fn map_state(state: &str) -> Option<Direction> {
     None
 }
 
-#[tauri::command]
-fn read_config(app_state: State<'_, Mutex<AppState>>, app_handle: tauri::AppHandle) {
+fn read_config_file() -> Option<String> {
     let home_dir = match std::env::var("HOME") {
         Ok(home_dir) => home_dir,
         Err(_) => {
             error!("Failed to read home env variable, using default");
-            return;
+            return None;
         }
     };
     let path = home_dir + "/.config/steamdeck-keyboard/config.json";
-    let config_str = match fs::read_to_string(path) {
-        Ok(config_str) => config_str,
+    return match fs::read_to_string(path) {
+        Ok(config_str) => Some(config_str),
         Err(_) => {
             error!("Failed to read config file, using default");
-            return;
+            None
         }
     };
-    let app_state = app_state.lock().unwrap();
+}
+
+#[tauri::command]
+fn read_config(app_state: State<'_, Mutex<AppState>>, app_handle: tauri::AppHandle) {
+    let config_str = match read_config_file() {
+        Some(config_str) => config_str,
+        None => return,
+    };
+    let mut app_state = app_state.lock().unwrap();
     app_handle
         .emit("config", config_str.clone())
         .expect("Should be able to set config");
-    app_state.config_tx.send(config_str).unwrap();
+    match serde_json::from_str::<SuspendRulesConfig>(&config_str) {
+        Ok(config) => app_state
+            .suspend_rules
+            .config_update(config.suspend_rules.unwrap_or_default()),
+        Err(e) => error!(
+            "Failed to parse suspend rules, keeping the previous ones: {}",
+            e
+        ),
+    }
+    send_plugin_command(&app_state, PluginCommand::Config(config_str));
+}
+
+/// Plugin names from `--plugin` arguments, e.g. `--plugin steamdeck,evdev`,
+/// otherwise from `plugins` in the config, defaulting to the steamdeck plugin.
+fn plugin_names() -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut args = std::env::args();
+    loop {
+        match args.next() {
+            Some(arg) if arg == "--plugin" => {}
+            Some(_) => continue,
+            None => break,
+        }
+        match args.next() {
+            Some(value) => names.extend(value.split(',').map(|name| name.trim().to_string())),
+            None => error!("Missing plugin name after --plugin"),
+        }
+    }
+    if !names.is_empty() {
+        return names;
+    }
+    let config_plugins = read_config_file()
+        .and_then(|config_str| serde_json::from_str::<PluginsConfig>(&config_str).ok())
+        .and_then(|config| config.plugins);
+    return match config_plugins {
+        Some(plugins) if !plugins.is_empty() => plugins,
+        _ => vec!["steamdeck".to_string()],
+    };
+}
+
+/// Keyboard backend from `keyboard_backend` in the config, defaulting to enigo.
+fn keyboard_backend() -> Box<dyn KeyboardBackend> {
+    let name = read_config_file()
+        .and_then(|config_str| serde_json::from_str::<KeyboardConfig>(&config_str).ok())
+        .and_then(|config| config.keyboard_backend)
+        .unwrap_or_default();
+    info!("Using keyboard backend {:?}", name);
+    return create_keyboard_backend(name);
 }
 
 #[tauri::command]
 fn send_key(app_state: State<'_, Mutex<AppState>>, key: &str, state: &str) {
-    println!("sending key {key}");
-    let mapped_key = map_key(key);
-    let mapped_direction = map_state(state);
-    if mapped_key.is_none() || mapped_direction.is_none() {
-        error!("Unknown key {} or state {}", key, state);
-        return;
-    }
+    let mapped_direction = match map_state(state) {
+        Some(mapped_direction) => mapped_direction,
+        None => return,
+    };
     debug!("key {} state {}", key, state);
     let mut app_state = app_state.lock().unwrap();
-    app_state
-        .enigo
-        .key(mapped_key.unwrap(), mapped_direction.unwrap())
-        .expect("Should be able to send key");
+    match app_state.keyboard.key(key, mapped_direction) {
+        Ok(_) => {}
+        Err(e) => error!("Failed to send key {}: {}", key, e),
+    }
 }
 
 #[tauri::command]
@@ -166,18 +174,134 @@ fn toggle_window(app_state: State<'_, Mutex<AppState>>, app_handle: tauri::AppHa
     }
     // pause/resume steam client/process
     let mut app_state = app_state.lock().unwrap();
-    if !is_visible {
-        app_state.pause_tx.send(false).unwrap();
-    } else {
-        app_state.pause_tx.send(true).unwrap();
-    }
+    app_state.suspend_rules.update(!is_visible);
+    send_plugin_command(&app_state, PluginCommand::Pause(is_visible));
     return !is_visible;
 }
 
+/// Sends command to all plugins, plugins ignore commands they don't support.
+fn send_plugin_command(app_state: &AppState, command: PluginCommand) {
+    for plugin in app_state.plugins.iter() {
+        match plugin.send(command.clone()) {
+            Ok(()) => {}
+            Err(e) => error!("Failed to send command to plugin: {}", e),
+        }
+    }
+}
+
+/// Sends haptic command to all plugins.
+fn send_haptic_command(app_state: &AppState, command: HapticCommand) {
+    send_plugin_command(app_state, PluginCommand::Haptic(command));
+}
+
+/// Releases held keys, resumes suspended processes and stops all plugins,
+/// the app exits once they returned.
+fn stop_plugins(app_handle: &tauri::AppHandle) {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    app_state.keyboard.release_all();
+    debug!("Resuming suspended processes");
+    app_state.suspend_rules.resume();
+    if app_state.plugins.is_empty() {
+        app_handle.exit(0);
+        return;
+    }
+    send_plugin_command(&app_state, PluginCommand::Stop);
+}
+
 #[tauri::command]
 fn trigger_haptic_pulse(app_state: State<'_, Mutex<AppState>>, pad: u8) {
     let app_state = app_state.lock().unwrap();
-    app_state.trigger_haptic_tx.send(pad).unwrap();
+    send_haptic_command(
+        &app_state,
+        HapticCommand::Pulse {
+            pad: pad,
+            pulse: HapticPulse::default(),
+        },
+    );
+}
+
+#[tauri::command]
+fn trigger_haptic(
+    app_state: State<'_, Mutex<AppState>>,
+    pad: u8,
+    duration: u16,
+    interval: u16,
+    count: u16,
+    gain: u8,
+) {
+    let app_state = app_state.lock().unwrap();
+    send_haptic_command(
+        &app_state,
+        HapticCommand::Pulse {
+            pad: pad,
+            pulse: HapticPulse {
+                duration: duration,
+                interval: interval,
+                count: count,
+                gain: gain,
+            },
+        },
+    );
+}
+
+#[tauri::command]
+fn trigger_haptic_preset(app_state: State<'_, Mutex<AppState>>, pad: u8, name: String) {
+    let app_state = app_state.lock().unwrap();
+    send_haptic_command(
+        &app_state,
+        HapticCommand::Preset {
+            pad: pad,
+            name: name,
+        },
+    );
+}
+
+#[tauri::command]
+fn play_haptic_pattern(app_state: State<'_, Mutex<AppState>>, name: String, queue: Option<bool>) {
+    let app_state = app_state.lock().unwrap();
+    send_haptic_command(
+        &app_state,
+        HapticCommand::Pattern {
+            name: name,
+            queue: queue.unwrap_or(false),
+        },
+    );
+}
+
+#[tauri::command]
+fn play_haptic_steps(
+    app_state: State<'_, Mutex<AppState>>,
+    steps: Vec<HapticStep>,
+    queue: Option<bool>,
+) {
+    let app_state = app_state.lock().unwrap();
+    send_haptic_command(
+        &app_state,
+        HapticCommand::Steps {
+            steps: steps,
+            queue: queue.unwrap_or(false),
+        },
+    );
+}
+
+#[tauri::command]
+fn cancel_haptic(app_state: State<'_, Mutex<AppState>>) {
+    let app_state = app_state.lock().unwrap();
+    send_haptic_command(&app_state, HapticCommand::Cancel);
+}
+
+#[tauri::command]
+fn trigger_rumble(app_state: State<'_, Mutex<AppState>>, left: u16, right: u16, duration_ms: u64) {
+    let app_state = app_state.lock().unwrap();
+    send_haptic_command(
+        &app_state,
+        HapticCommand::Rumble(Rumble {
+            left: left,
+            right: right,
+            duration_ms: duration_ms,
+        }),
+    );
 }
 
 #[tauri::command]
@@ -195,20 +319,37 @@ fn log(level: &str, message: &str) {
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     env_logger::init();
+    // before tauri starts its threads, the watcher is forked
+    plugin::install_resume_guard();
     tauri::Builder::default()
         .setup(|app| {
-            let (config_tx, config_rx) = mpsc::channel::<String>();
-            let (pause_tx, pause_rx) = mpsc::channel::<bool>();
-            let (stop_tx, stop_rx) = mpsc::channel::<()>();
-            let (trigger_haptic_tx, trigger_haptic_rx) = mpsc::channel::<u8>();
-            let plugin = Box::new(plugin::SteamdeckPlugin::new());
-            let enigo = Enigo::new(&Settings::default()).unwrap();
+            let mut plugin_command_txs = Vec::new();
+            let mut plugin_threads = Vec::new();
+            for name in plugin_names() {
+                let plugin = match plugin::create_plugin(&name) {
+                    Some(plugin) => plugin,
+                    None => {
+                        error!("Unknown plugin {}", name);
+                        continue;
+                    }
+                };
+                info!("Starting plugin {}", name);
+                let (command_tx, command_rx) = unbounded_channel::<PluginCommand>();
+                plugin_command_txs.push(command_tx);
+                plugin_threads.push((name, plugin, command_rx));
+            }
             app.manage(Mutex::new(AppState {
-                enigo: Enigo::new(&Settings::default()).unwrap(),
-                pause_tx: pause_tx,
-                config_tx: config_tx,
-                trigger_haptic_tx: trigger_haptic_tx,
+                keyboard: keyboard_backend(),
+                plugins: plugin_command_txs,
+                suspend_rules: SuspendRules::new(),
             }));
+            // suspend rules find restarted processes, e.g. a daemon restarted by systemd
+            let app_handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(Duration::from_secs(1));
+                let state = app_handle.state::<Mutex<AppState>>();
+                state.lock().unwrap().suspend_rules.handle_exits();
+            });
             // signal handler
             let app_handle = app.handle().clone();
             let mut signals =
@@ -217,28 +358,8 @@ pub fn run() {
                 for signal in signals.forever() {
                     match signal {
                         SIGINT | SIGTERM => {
-                            // TODO: this doesn't work
-                            debug!("Releaseing keys before stopping process");
-                            let mut enigo = Enigo::new(&Settings::default()).unwrap();
-                            for held_key in enigo.held().0 {
-                                debug!("Releasing held key");
-                                enigo
-                                    .key(held_key, Direction::Release)
-                                    .expect("Could not release held key");
-                            }
-                            enigo
-                                .key(Key::Shift, Direction::Release)
-                                .expect("Should be able to release shift key");
-                            enigo
-                                .key(Key::Alt, Direction::Release)
-                                .expect("Should be able to release alt key");
-                            enigo
-                                .key(Key::Control, Direction::Release)
-                                .expect("Should be able to release control key");
-                            enigo
-                                .key(Key::Meta, Direction::Release)
-                                .expect("Should be able to release meta key");
-                            stop_tx.send(()).unwrap();
+                            debug!("Releasing keys before stopping process");
+                            stop_plugins(&app_handle);
                         }
                         _ => unreachable!(),
                     }
@@ -250,15 +371,29 @@ pub fn run() {
                 .expect("Should be able to set fullscreen");
             win.set_always_on_top(true)
                 .expect("Should be able to set always on top");
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn(plugin_thread(
-                plugin,
-                app_handle,
-                config_rx,
-                pause_rx,
-                stop_rx,
-                trigger_haptic_rx,
-            ));
+            let plugin_handles: Vec<_> = plugin_threads
+                .into_iter()
+                .map(|(name, plugin, command_rx)| {
+                    let app_handle = app.handle().clone();
+                    // plugins block their thread, keep them off the async workers
+                    tauri::async_runtime::spawn_blocking(move || {
+                        plugin_thread(name, plugin, app_handle, command_rx)
+                    })
+                })
+                .collect();
+            if !plugin_handles.is_empty() {
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    for plugin_handle in plugin_handles {
+                        match plugin_handle.await {
+                            Ok(()) => {}
+                            Err(e) => error!("Plugin thread panicked: {}", e),
+                        }
+                    }
+                    debug!("All plugins stopped, exiting now");
+                    app_handle.exit(0);
+                });
+            }
             let quit_menu_item = MenuItem::with_id(app, "Quit", "Quit", true, None::<&str>)?;
             let menu = Menu::with_items(app, &[&quit_menu_item])?;
             let tray = TrayIconBuilder::new()
@@ -267,7 +402,7 @@ pub fn run() {
                 .on_menu_event(|app, event| match event.id.as_ref() {
                     "Quit" => {
                         debug!("Quit menu item was clicked");
-                        app.exit(0);
+                        stop_plugins(app);
                     }
                     _ => {
                         error!("Menu item {:?} not handled", event.id);
@@ -282,19 +417,81 @@ pub fn run() {
             send_key,
             toggle_window,
             trigger_haptic_pulse,
+            trigger_haptic,
+            trigger_haptic_preset,
+            play_haptic_pattern,
+            play_haptic_steps,
+            cancel_haptic,
+            trigger_rumble,
             log,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-async fn plugin_thread(
-    mut plugin: Box<dyn plugin::Plugin>,
+/// Runs plugin until stopped and forwards its events from a separate thread.
+fn plugin_thread(
+    name: String,
+    mut plugin: Box<dyn Plugin>,
     app_handle: tauri::AppHandle,
-    config_rx: mpsc::Receiver<String>,
-    pause_rx: mpsc::Receiver<bool>,
-    stop_rx: mpsc::Receiver<()>,
-    trigger_haptic_rx: mpsc::Receiver<u8>,
+    command_rx: UnboundedReceiver<PluginCommand>,
 ) {
-    plugin.thread_fn(app_handle, config_rx, pause_rx, stop_rx, trigger_haptic_rx);
+    let (event_tx, event_rx) = mpsc::channel::<PluginEvent>();
+    let dispatch_app_handle = app_handle.clone();
+    let dispatch_name = name.clone();
+    std::thread::spawn(move || {
+        for event in event_rx {
+            dispatch_event(&dispatch_app_handle, &dispatch_name, event);
+        }
+    });
+    match plugin.run(command_rx, event_tx) {
+        Ok(()) => debug!("Plugin {} stopped", name),
+        Err(e) => error!("Plugin {} failed: {}", name, e),
+    }
+}
+
+fn dispatch_event(app_handle: &tauri::AppHandle, source: &str, event: PluginEvent) {
+    match event {
+        PluginEvent::Input(device_report) => app_handle
+            .emit("input", device_report)
+            .expect("Should be able to emit device report"),
+        PluginEvent::DeviceConnected(name) => app_handle
+            .emit(
+                "device-connected",
+                DeviceEvent {
+                    source: source.to_string(),
+                    name: name,
+                },
+            )
+            .expect("Should be able to emit device connected"),
+        PluginEvent::DeviceDisconnected(name) => app_handle
+            .emit(
+                "device-disconnected",
+                DeviceEvent {
+                    source: source.to_string(),
+                    name: name,
+                },
+            )
+            .expect("Should be able to emit device disconnected"),
+        PluginEvent::DeviceStatus(status) => app_handle
+            .emit("device-status", status)
+            .expect("Should be able to emit device status"),
+        PluginEvent::ToggleWindow => {
+            toggle_window(app_handle.state::<Mutex<AppState>>(), app_handle.clone());
+        }
+        PluginEvent::SwitchLayer(layer) => app_handle
+            .emit("switch-layer", layer)
+            .expect("Should be able to emit layer switch"),
+        PluginEvent::SendKey(key) => {
+            send_key(app_handle.state::<Mutex<AppState>>(), &key, "down");
+            send_key(app_handle.state::<Mutex<AppState>>(), &key, "up");
+        }
+        PluginEvent::MoveWindow { x, y } => match app_handle.get_webview_window("main") {
+            Some(win) => match win.set_position(tauri::LogicalPosition::new(x, y)) {
+                Ok(_) => {}
+                Err(e) => error!("Failed to move window {}", e),
+            },
+            None => error!("Window to move not found"),
+        },
+    }
 }
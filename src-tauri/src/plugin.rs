@@ -1,16 +1,124 @@
+mod evdev;
+mod gesture;
+mod haptic;
+mod hid;
+mod process;
+mod sony;
 mod steamdeck;
+mod suspend;
+mod touch;
 
-use std::sync::mpsc;
+use std::{
+    fmt,
+    future::{self, Future},
+    sync::mpsc,
+    time::Instant,
+};
+use tokio::sync::mpsc::UnboundedReceiver;
 
-pub use steamdeck::SteamdeckPlugin;
+pub use haptic::{HapticCommand, HapticPulse, HapticStep, Rumble};
+pub use process::install_resume_guard;
+pub use steamdeck::report::{DeviceStatus, SteamDeckDeviceReport};
+pub use suspend::{SuspendRule, SuspendRules};
+
+use evdev::EvdevPlugin;
+use sony::SonyPlugin;
+use steamdeck::SteamdeckPlugin;
+
+/// Plugins by name, selected with `--plugin` or `plugins` in the config.
+/// The name is also the `source` of emitted input.
+pub static PLUGINS: &[(&str, fn() -> Box<dyn Plugin>)] = &[
+    (steamdeck::NAME, || Box::new(SteamdeckPlugin::new())),
+    (steamdeck::REPLAY_NAME, || {
+        Box::new(SteamdeckPlugin::replay())
+    }),
+    (sony::NAME, || Box::new(SonyPlugin::new())),
+    (evdev::NAME, || Box::new(EvdevPlugin::new())),
+];
+
+pub fn create_plugin(name: &str) -> Option<Box<dyn Plugin>> {
+    return PLUGINS
+        .iter()
+        .find(|(plugin_name, _)| *plugin_name == name)
+        .map(|(_, factory)| factory());
+}
+
+/// Sent from the app to every running plugin.
+#[derive(Debug, Clone)]
+pub enum PluginCommand {
+    /// content of the config file
+    Config(String),
+    /// true if the keyboard was hidden, false if shown
+    Pause(bool),
+    Haptic(HapticCommand),
+    /// release resources and return from `Plugin::run`
+    Stop,
+}
+
+/// Sent from a plugin to the app.
+#[derive(Debug)]
+pub enum PluginEvent {
+    Input(SteamDeckDeviceReport),
+    /// device with its name was (re)connected
+    DeviceConnected(String),
+    /// device with its name was disconnected
+    DeviceDisconnected(String),
+    /// battery level and charging state changed
+    DeviceStatus(DeviceStatus),
+    /// show or hide the keyboard, the new state is sent back as `PluginCommand::Pause`
+    ToggleWindow,
+    SwitchLayer(String),
+    /// press and release key
+    SendKey(String),
+    MoveWindow {
+        x: f64,
+        y: f64,
+    },
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+    /// the app no longer receives events
+    EventsClosed,
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            PluginError::EventsClosed => write!(f, "event channel closed"),
+        };
+    }
+}
 
 pub trait Plugin: Send {
-    fn thread_fn(
+    /// Reads input and handles commands until `PluginCommand::Stop`
+    /// or until the command channel is closed.
+    /// Runs on its own thread and may block it.
+    fn run(
         &mut self,
-        app_handle: tauri::AppHandle,
-        config_rx: mpsc::Receiver<String>,
-        pause_rx: mpsc::Receiver<bool>,
-        stop_rx: mpsc::Receiver<()>,
-        trigger_haptic_rx: mpsc::Receiver<u8>,
-    );
+        commands: UnboundedReceiver<PluginCommand>,
+        events: mpsc::Sender<PluginEvent>,
+    ) -> Result<(), PluginError>;
+}
+
+fn send_event(events: &mpsc::Sender<PluginEvent>, event: PluginEvent) -> Result<(), PluginError> {
+    return events.send(event).map_err(|_| PluginError::EventsClosed);
+}
+
+/// Runs plugin loop on a single threaded runtime on the current plugin thread,
+/// with io for watching fds and time for timers.
+fn block_on<F: Future>(plugin_loop: F) -> F::Output {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Should be able to build plugin runtime");
+    return runtime.block_on(plugin_loop);
+}
+
+/// Waits until deadline, forever without deadline.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => future::pending().await,
+    }
 }
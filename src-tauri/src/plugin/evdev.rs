@@ -0,0 +1,548 @@
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::{future, io, sync::mpsc, time::Instant};
+use tokio::{
+    io::{unix::AsyncFd, Interest},
+    sync::mpsc::UnboundedReceiver,
+};
+
+use super::gesture::ClosePolicy;
+use super::steamdeck::report::{SteamDeckButton, SteamDeckDeviceReport};
+use super::touch::{pad_position, Reconnect, TouchConfig, TouchPipeline};
+use super::{block_on, send_event, sleep_until, Plugin, PluginCommand, PluginError, PluginEvent};
+
+mod device;
+
+use device::{
+    find_multitouch_device, EvdevDevice, MultitouchSnapshot, ABS_MT_POSITION_X, ABS_MT_POSITION_Y,
+    ABS_MT_SLOT, ABS_MT_TRACKING_ID, BTN_LEFT, EV_ABS, EV_KEY, EV_SYN, SYN_DROPPED, SYN_REPORT,
+};
+
+pub const NAME: &str = "evdev";
+
+/// How touches are assigned to the left and right pad.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchSplit {
+    /// touches on the left half are the left pad, on the right half the right pad
+    #[default]
+    Halves,
+    /// first touch is the left pad, second touch the right pad, using the whole surface
+    Slots,
+}
+
+/// What counts as pressing a key.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchClick {
+    /// touchpad button click
+    #[default]
+    Button,
+    /// touching, e.g. for touchscreens
+    Touch,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct Config {
+    #[serde(flatten)]
+    touch: TouchConfig,
+    /// device path or part of the device name, first multitouch device if not set
+    evdev_device: Option<String>,
+    evdev_split: Option<TouchSplit>,
+    evdev_click: Option<TouchClick>,
+    /// grab the device while the keyboard is visible
+    evdev_grab: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// -1 if not touched
+    tracking_id: i32,
+    x: i32,
+    y: i32,
+}
+
+/// Touch state of multitouch protocol B slots.
+struct MultitouchState {
+    slots: Vec<Slot>,
+    slot: usize,
+    button: bool,
+    /// events are discarded from SYN_DROPPED until the next SYN_REPORT
+    is_dropped: bool,
+    /// slots have to be read from the kernel before the next report
+    needs_resync: bool,
+}
+
+impl MultitouchState {
+    fn new(slots: usize) -> Self {
+        return Self {
+            slots: vec![
+                Slot {
+                    tracking_id: -1,
+                    x: 0,
+                    y: 0,
+                };
+                slots
+            ],
+            slot: 0,
+            button: false,
+            is_dropped: false,
+            needs_resync: false,
+        };
+    }
+
+    /// Applies event, returns true once a frame is complete.
+    /// Check `needs_resync` before reporting a complete frame.
+    fn update(&mut self, event: &libc::input_event) -> bool {
+        match (event.type_, event.code) {
+            (EV_SYN, SYN_DROPPED) => {
+                warn!("[evdev] events dropped, resyncing");
+                self.is_dropped = true;
+            }
+            (EV_SYN, SYN_REPORT) if self.is_dropped => {
+                self.is_dropped = false;
+                self.needs_resync = true;
+                return true;
+            }
+            _ if self.is_dropped => {}
+            (EV_SYN, SYN_REPORT) => return true,
+            (EV_KEY, BTN_LEFT) => self.button = event.value != 0,
+            (EV_ABS, ABS_MT_SLOT) => self.slot = event.value.max(0) as usize,
+            (EV_ABS, code) => match self.slots.get_mut(self.slot) {
+                Some(slot) => match code {
+                    ABS_MT_TRACKING_ID => slot.tracking_id = event.value,
+                    ABS_MT_POSITION_X => slot.x = event.value,
+                    ABS_MT_POSITION_Y => slot.y = event.value,
+                    _ => {}
+                },
+                None => {}
+            },
+            _ => {}
+        }
+        return false;
+    }
+
+    /// Reads the state of all slots from the kernel after events were dropped,
+    /// releases all touches if that fails.
+    fn resync(&mut self, device: &EvdevDevice) {
+        self.needs_resync = false;
+        match device.multitouch_snapshot() {
+            Ok(snapshot) => self.apply_snapshot(&snapshot),
+            Err(e) => {
+                warn!("[evdev] failed to resync, releasing all touches: {}", e);
+                *self = Self::new(self.slots.len());
+            }
+        }
+    }
+
+    fn apply_snapshot(&mut self, snapshot: &MultitouchSnapshot) {
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            slot.tracking_id = snapshot.tracking_ids.get(i).copied().unwrap_or(-1);
+            slot.x = snapshot.x.get(i).copied().unwrap_or(0);
+            slot.y = snapshot.y.get(i).copied().unwrap_or(0);
+        }
+        self.slot = snapshot.slot.max(0) as usize;
+        self.button = snapshot.button;
+    }
+
+    fn report(
+        &self,
+        device: &EvdevDevice,
+        split: TouchSplit,
+        click: TouchClick,
+    ) -> SteamDeckDeviceReport {
+        let mut report = SteamDeckDeviceReport::default();
+        let touches = self.slots.iter().filter(|slot| slot.tracking_id >= 0);
+        let mut is_l_touched = false;
+        let mut is_r_touched = false;
+        for (i, slot) in touches.enumerate() {
+            let rel_x = device.x.relative(slot.x);
+            let rel_y = device.y.relative(slot.y);
+            let (is_left, rel_x) = match split {
+                TouchSplit::Halves if rel_x < 0.5 => (true, rel_x * 2.0),
+                TouchSplit::Halves => (false, (rel_x - 0.5) * 2.0),
+                TouchSplit::Slots => (i == 0, rel_x),
+            };
+            if (is_left && is_l_touched) || (!is_left && is_r_touched) {
+                continue;
+            }
+            let position = pad_position(rel_x, rel_y);
+            if is_left {
+                (report.l_pad_x, report.l_pad_y) = position;
+                is_l_touched = true;
+            } else {
+                (report.r_pad_x, report.r_pad_y) = position;
+                is_r_touched = true;
+            }
+        }
+        let is_clicked = match click {
+            TouchClick::Button => self.button,
+            TouchClick::Touch => true,
+        };
+        report.buttons.set(SteamDeckButton::LPadTouch, is_l_touched);
+        report.buttons.set(SteamDeckButton::RPadTouch, is_r_touched);
+        report
+            .buttons
+            .set(SteamDeckButton::LPadClick, is_l_touched && is_clicked);
+        report
+            .buttons
+            .set(SteamDeckButton::RPadClick, is_r_touched && is_clicked);
+        // no usable pressure, clicks are full force
+        report.l_pad_force = if is_l_touched && is_clicked {
+            i16::MAX as u16
+        } else {
+            0
+        };
+        report.r_pad_force = if is_r_touched && is_clicked {
+            i16::MAX as u16
+        } else {
+            0
+        };
+        return report;
+    }
+}
+
+/// Linux evdev multitouch devices, e.g. touchscreens and laptop touchpads.
+pub struct EvdevPlugin {
+    config: Config,
+    touch: TouchPipeline,
+    /// registered with the plugin runtime, readable once events arrive
+    device: Option<AsyncFd<EvdevDevice>>,
+    multitouch: MultitouchState,
+    is_grabbed: bool,
+    /// grabbing failed, not retried until the device is reopened
+    grab_failed: bool,
+    reconnect: Reconnect,
+}
+
+impl EvdevPlugin {
+    pub fn new() -> Self {
+        return Self {
+            config: Config::default(),
+            touch: TouchPipeline::new(),
+            device: None,
+            multitouch: MultitouchState::new(0),
+            is_grabbed: false,
+            grab_failed: false,
+            reconnect: Reconnect::new(),
+        };
+    }
+}
+
+impl Plugin for EvdevPlugin {
+    fn run(
+        &mut self,
+        commands: UnboundedReceiver<PluginCommand>,
+        events: mpsc::Sender<PluginEvent>,
+    ) -> Result<(), PluginError> {
+        return block_on(plugin_loop(self, commands, &events));
+    }
+}
+
+/// Handles commands, evdev events and reconnects as soon as they arrive,
+/// returns once stopped.
+async fn plugin_loop(
+    plugin: &mut EvdevPlugin,
+    mut commands: UnboundedReceiver<PluginCommand>,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    loop {
+        let reconnect_deadline = match plugin.device {
+            Some(_) => None,
+            None => Some(plugin.reconnect.deadline()),
+        };
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(PluginCommand::Stop) | None => {
+                    update_grab(plugin, false);
+                    return Ok(());
+                }
+                Some(command) => handle_command(plugin, command),
+            },
+            input_events = read_device(&plugin.device) => match input_events {
+                Ok(input_events) => handle_input_events(plugin, events, &input_events)?,
+                Err(e) => {
+                    warn!("Failed to read device, reconnecting: {}", e);
+                    let name = match plugin.device.take() {
+                        Some(device) => device.into_inner().name,
+                        None => String::new(),
+                    };
+                    plugin.is_grabbed = false;
+                    plugin.reconnect.reset();
+                    send_event(events, PluginEvent::DeviceDisconnected(name))?;
+                }
+            },
+            _ = sleep_until(reconnect_deadline) => {}
+        }
+        if plugin.device.is_none() && plugin.reconnect.is_due(Instant::now()) {
+            reconnect(plugin, events)?;
+        }
+        let grab = plugin.config.evdev_grab.unwrap_or(true) && !plugin.touch.is_paused();
+        update_grab(plugin, grab);
+    }
+}
+
+fn handle_command(plugin: &mut EvdevPlugin, command: PluginCommand) {
+    match command {
+        PluginCommand::Pause(message_pause) => {
+            debug!("[evdev] new pause flag: {}", message_pause);
+            plugin.touch.pause_update(message_pause);
+        }
+        PluginCommand::Config(config_str) => config_update(plugin, config_str),
+        PluginCommand::Haptic(command) => {
+            trace!("[evdev] haptic not supported: {:?}", command)
+        }
+        // handled by the plugin loop
+        PluginCommand::Stop => {}
+    }
+}
+
+/// Waits until the device is readable and reads all available events,
+/// never returns without device.
+async fn read_device(device: &Option<AsyncFd<EvdevDevice>>) -> io::Result<Vec<libc::input_event>> {
+    let device = match device {
+        Some(device) => device,
+        None => return future::pending().await,
+    };
+    loop {
+        let mut guard = device.readable().await?;
+        match guard.try_io(|device| device.get_ref().read_events()) {
+            Ok(result) => return result,
+            // readiness was cleared, wait again
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+/// Applies events to the multitouch state, handles the report of the last complete frame.
+fn handle_input_events(
+    plugin: &mut EvdevPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+    input_events: &[libc::input_event],
+) -> Result<(), PluginError> {
+    let device = match &plugin.device {
+        Some(device) => device.get_ref(),
+        None => return Ok(()),
+    };
+    let split = plugin.config.evdev_split.unwrap_or_default();
+    let click = plugin.config.evdev_click.unwrap_or_default();
+    let now = Instant::now();
+    let mut device_report = None;
+    for event in input_events {
+        if plugin.multitouch.update(event) {
+            if plugin.multitouch.needs_resync {
+                plugin.multitouch.resync(device);
+            }
+            let mut report = plugin.multitouch.report(device, split, click);
+            report.source = NAME;
+            device_report = Some(report);
+        }
+    }
+    return match device_report {
+        Some(device_report) => handle_report(plugin, events, device_report, now),
+        None => Ok(()),
+    };
+}
+
+fn handle_report(
+    plugin: &mut EvdevPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+    device_report: SteamDeckDeviceReport,
+    now: Instant,
+) -> Result<(), PluginError> {
+    let touch_update = plugin
+        .touch
+        .handle_report(events, device_report, false, now)?;
+    for name in touch_update.haptic_patterns {
+        trace!("[evdev] haptic pattern {} not supported", name);
+    }
+    return Ok(());
+}
+
+fn update_grab(plugin: &mut EvdevPlugin, grab: bool) {
+    if plugin.is_grabbed == grab || (grab && plugin.grab_failed) {
+        return;
+    }
+    let device = match &plugin.device {
+        Some(device) => device.get_ref(),
+        None => return,
+    };
+    match device.grab(grab) {
+        Ok(()) => {
+            debug!("[evdev] grabbed {}: {}", device.name, grab);
+            plugin.is_grabbed = grab;
+        }
+        Err(e) if grab => {
+            error!("Failed to grab {}: {}", device.name, e);
+            // don't retry on every loop
+            plugin.grab_failed = true;
+        }
+        Err(e) => {
+            // closing the device releases the grab
+            error!("Failed to ungrab {}, reopening it: {}", device.name, e);
+            plugin.device = None;
+            plugin.is_grabbed = false;
+            plugin.reconnect.reset();
+        }
+    }
+}
+
+fn config_update(plugin: &mut EvdevPlugin, config_str: String) {
+    let evdev_device = plugin.config.evdev_device.clone();
+    plugin.config = match serde_json::from_str(config_str.as_str()) {
+        Ok(config) => config,
+        Err(e) => {
+            error!(
+                "[evdev] Failed to parse config, keeping the previous one: {}",
+                e
+            );
+            return;
+        }
+    };
+    if plugin.config.evdev_device != evdev_device {
+        debug!("[evdev] device changed, reconnecting");
+        update_grab(plugin, false);
+        plugin.device = None;
+        plugin.is_grabbed = false;
+        plugin.reconnect.reset();
+    }
+    // a touchscreen releases after every tap, so it would close after every key
+    let default_close_policy = match plugin.config.evdev_click {
+        Some(TouchClick::Touch) => ClosePolicy::Explicit,
+        _ => ClosePolicy::OnRelease,
+    };
+    plugin
+        .touch
+        .config_update(&plugin.config.touch, default_close_policy);
+}
+
+/// Reopens the device, retrying with exponential backoff.
+fn reconnect(
+    plugin: &mut EvdevPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    let evdev_device = plugin.config.evdev_device.as_deref();
+    let device = plugin.reconnect.attempt(NAME, || {
+        let device = find_multitouch_device(evdev_device)?;
+        let name = device.name.clone();
+        return match AsyncFd::with_interest(device, Interest::READABLE) {
+            Ok(device) => Some(device),
+            Err(e) => {
+                error!("Failed to watch {}: {}", name, e);
+                None
+            }
+        };
+    });
+    match device {
+        Some(device) => {
+            info!(
+                "[evdev] device connected: {} {:?}",
+                device.get_ref().name,
+                device.get_ref().path
+            );
+            plugin.multitouch = MultitouchState::new(device.get_ref().slots);
+            plugin.grab_failed = false;
+            send_event(
+                events,
+                PluginEvent::DeviceConnected(device.get_ref().name.clone()),
+            )?;
+            plugin.device = Some(device);
+        }
+        None => {}
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn event(type_: u16, code: u16, value: i32) -> libc::input_event {
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        event.type_ = type_;
+        event.code = code;
+        event.value = value;
+        return event;
+    }
+
+    fn touch(multitouch: &mut MultitouchState, slot: i32, tracking_id: i32, x: i32, y: i32) {
+        multitouch.update(&event(EV_ABS, ABS_MT_SLOT, slot));
+        multitouch.update(&event(EV_ABS, ABS_MT_TRACKING_ID, tracking_id));
+        multitouch.update(&event(EV_ABS, ABS_MT_POSITION_X, x));
+        multitouch.update(&event(EV_ABS, ABS_MT_POSITION_Y, y));
+    }
+
+    #[test]
+    fn discards_events_until_report_after_dropped() {
+        let mut multitouch = MultitouchState::new(2);
+        touch(&mut multitouch, 0, 1, 100, 200);
+        assert!(multitouch.update(&event(EV_SYN, SYN_REPORT, 0)));
+        assert!(!multitouch.update(&event(EV_SYN, SYN_DROPPED, 0)));
+        touch(&mut multitouch, 1, 2, 300, 400);
+        multitouch.update(&event(EV_KEY, BTN_LEFT, 1));
+        assert_eq!(multitouch.slots[1].tracking_id, -1);
+        assert!(!multitouch.button);
+        assert!(!multitouch.needs_resync);
+        assert!(multitouch.update(&event(EV_SYN, SYN_REPORT, 0)));
+        assert!(multitouch.needs_resync);
+        // applied again once resynced
+        touch(&mut multitouch, 1, 2, 300, 400);
+        assert_eq!(multitouch.slots[1].tracking_id, 2);
+    }
+
+    #[test]
+    fn applies_snapshot_after_dropped() {
+        let mut multitouch = MultitouchState::new(2);
+        touch(&mut multitouch, 0, 1, 100, 200);
+        multitouch.update(&event(EV_SYN, SYN_REPORT, 0));
+        multitouch.update(&event(EV_SYN, SYN_DROPPED, 0));
+        multitouch.update(&event(EV_SYN, SYN_REPORT, 0));
+        // first touch lifted and second touch down while events were dropped
+        multitouch.apply_snapshot(&MultitouchSnapshot {
+            slot: 1,
+            tracking_ids: vec![-1, 2],
+            x: vec![100, 300],
+            y: vec![200, 400],
+            button: true,
+        });
+        assert_eq!(multitouch.slots[0].tracking_id, -1);
+        let slot = &multitouch.slots[1];
+        assert_eq!((slot.tracking_id, slot.x, slot.y), (2, 300, 400));
+        assert_eq!(multitouch.slot, 1);
+        assert!(multitouch.button);
+        // events continue in the current slot
+        multitouch.update(&event(EV_ABS, ABS_MT_POSITION_X, 350));
+        assert_eq!(multitouch.slots[1].x, 350);
+    }
+
+    /// Taps the left half of the touchscreen once, returns if the keyboard was toggled.
+    fn tap_toggles(config: &str) -> bool {
+        let mut plugin = EvdevPlugin::new();
+        config_update(&mut plugin, config.to_string());
+        let (events, received) = mpsc::channel();
+        let start = Instant::now();
+        let touched = SteamDeckDeviceReport {
+            l_pad_x: -16000,
+            l_pad_y: 8000,
+            l_pad_force: i16::MAX as u16,
+            ..Default::default()
+        };
+        for (report, ms) in [(touched, 10), (SteamDeckDeviceReport::default(), 60)] {
+            let now = start + Duration::from_millis(ms);
+            handle_report(&mut plugin, &events, report, now).unwrap();
+        }
+        return received
+            .try_iter()
+            .any(|event| matches!(event, PluginEvent::ToggleWindow));
+    }
+
+    #[test]
+    fn touch_click_keeps_keyboard_visible_after_tap() {
+        assert!(!tap_toggles(r#"{"evdev_click": "touch"}"#));
+        assert!(tap_toggles(
+            r#"{"evdev_click": "touch", "close_policy": "on_release"}"#
+        ));
+        assert!(tap_toggles("{}"));
+    }
+}
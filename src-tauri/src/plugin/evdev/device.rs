@@ -0,0 +1,269 @@
+use log::{debug, trace};
+use std::{
+    fs::{self, File, OpenOptions},
+    io, mem,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+};
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+pub const SYN_REPORT: u16 = 0x00;
+pub const SYN_DROPPED: u16 = 0x03;
+pub const BTN_LEFT: u16 = 0x110;
+pub const ABS_MT_SLOT: u16 = 0x2f;
+pub const ABS_MT_POSITION_X: u16 = 0x35;
+pub const ABS_MT_POSITION_Y: u16 = 0x36;
+pub const ABS_MT_TRACKING_ID: u16 = 0x39;
+const ABS_CNT: usize = 0x40;
+const KEY_CNT: usize = 0x300;
+
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+/// _IOC for the evdev ioctl type 'E', see linux/input.h
+const fn evioc(dir: u32, nr: u32, size: usize) -> libc::c_ulong {
+    return (dir << 30 | (size as u32) << 16 | (b'E' as u32) << 8 | nr) as libc::c_ulong;
+}
+
+const fn eviocgname(len: usize) -> libc::c_ulong {
+    return evioc(IOC_READ, 0x06, len);
+}
+
+const fn eviocgkey(len: usize) -> libc::c_ulong {
+    return evioc(IOC_READ, 0x18, len);
+}
+
+const fn eviocgmtslots(len: usize) -> libc::c_ulong {
+    return evioc(IOC_READ, 0x0a, len);
+}
+
+const fn eviocgbit(ev: u16, len: usize) -> libc::c_ulong {
+    return evioc(IOC_READ, 0x20 + ev as u32, len);
+}
+
+const fn eviocgabs(abs: u16) -> libc::c_ulong {
+    return evioc(
+        IOC_READ,
+        0x40 + abs as u32,
+        mem::size_of::<libc::input_absinfo>(),
+    );
+}
+
+const EVIOCGRAB: libc::c_ulong = evioc(IOC_WRITE, 0x90, mem::size_of::<libc::c_int>());
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AbsRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl AbsRange {
+    /// Position relative to the range, in [0, 1].
+    pub fn relative(&self, value: i32) -> f32 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        return ((value - self.min) as f32 / (self.max - self.min) as f32).clamp(0.0, 1.0);
+    }
+}
+
+/// Current state of all slots as known by the kernel.
+#[derive(Debug, Clone, Default)]
+pub struct MultitouchSnapshot {
+    pub slot: i32,
+    pub tracking_ids: Vec<i32>,
+    pub x: Vec<i32>,
+    pub y: Vec<i32>,
+    pub button: bool,
+}
+
+/// Multitouch (protocol B) evdev device, read with raw ioctls.
+pub struct EvdevDevice {
+    file: File,
+    pub path: PathBuf,
+    pub name: String,
+    pub x: AbsRange,
+    pub y: AbsRange,
+    pub slots: usize,
+}
+
+impl EvdevDevice {
+    /// Opens the device, fails if it doesn't report multitouch slots.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        let fd = file.as_raw_fd();
+        let mut name = [0u8; 256];
+        ioctl(fd, eviocgname(name.len()), name.as_mut_ptr())?;
+        let name_len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..name_len]).to_string();
+        let mut abs_bits = [0u8; ABS_CNT / 8];
+        ioctl(fd, eviocgbit(EV_ABS, abs_bits.len()), abs_bits.as_mut_ptr())?;
+        let has_abs = |code: u16| abs_bits[code as usize / 8] & (1 << (code % 8)) > 0;
+        if !has_abs(ABS_MT_SLOT) || !has_abs(ABS_MT_POSITION_X) || !has_abs(ABS_MT_POSITION_Y) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} is not a multitouch device", name),
+            ));
+        }
+        let slot = abs_range(fd, ABS_MT_SLOT)?;
+        return Ok(Self {
+            path: path.to_path_buf(),
+            name: name,
+            x: abs_range(fd, ABS_MT_POSITION_X)?,
+            y: abs_range(fd, ABS_MT_POSITION_Y)?,
+            slots: (slot.max.max(0) + 1) as usize,
+            file: file,
+        });
+    }
+
+    /// Grabs the device exclusively, so touches don't move the mouse or click through.
+    pub fn grab(&self, grab: bool) -> io::Result<()> {
+        let fd = self.file.as_raw_fd();
+        let res = unsafe { libc::ioctl(fd, EVIOCGRAB as _, grab as libc::c_int) };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    /// Reads the slot state from the kernel, e.g. to resync after SYN_DROPPED.
+    pub fn multitouch_snapshot(&self) -> io::Result<MultitouchSnapshot> {
+        let fd = self.file.as_raw_fd();
+        let mut keys = [0u8; KEY_CNT / 8];
+        ioctl(fd, eviocgkey(keys.len()), keys.as_mut_ptr())?;
+        return Ok(MultitouchSnapshot {
+            slot: abs_info(fd, ABS_MT_SLOT)?.value,
+            tracking_ids: self.slot_values(ABS_MT_TRACKING_ID)?,
+            x: self.slot_values(ABS_MT_POSITION_X)?,
+            y: self.slot_values(ABS_MT_POSITION_Y)?,
+            button: keys[BTN_LEFT as usize / 8] & (1 << (BTN_LEFT % 8)) > 0,
+        });
+    }
+
+    /// Value of `code` for each slot.
+    fn slot_values(&self, code: u16) -> io::Result<Vec<i32>> {
+        // struct input_mt_request_layout, the code followed by a value per slot
+        let mut request = vec![0i32; self.slots + 1];
+        request[0] = code as i32;
+        ioctl(
+            self.file.as_raw_fd(),
+            eviocgmtslots(request.len() * mem::size_of::<i32>()),
+            request.as_mut_ptr(),
+        )?;
+        request.remove(0);
+        return Ok(request);
+    }
+
+    /// Reads all available events without blocking,
+    /// `WouldBlock` if there are none.
+    pub fn read_events(&self) -> io::Result<Vec<libc::input_event>> {
+        let fd = self.file.as_raw_fd();
+        let mut events = Vec::new();
+        let mut buf: [libc::input_event; 64] = unsafe { mem::zeroed() };
+        loop {
+            let res = unsafe {
+                libc::read(
+                    fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    mem::size_of_val(&buf),
+                )
+            };
+            if res < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::WouldBlock && !events.is_empty() {
+                    break;
+                }
+                return Err(err);
+            }
+            // evdev reads never return 0 bytes while the device exists
+            if res == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "device removed",
+                ));
+            }
+            let count = res as usize / mem::size_of::<libc::input_event>();
+            events.extend_from_slice(&buf[..count]);
+            if count < buf.len() {
+                break;
+            }
+        }
+        return Ok(events);
+    }
+}
+
+impl AsRawFd for EvdevDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        return self.file.as_raw_fd();
+    }
+}
+
+fn ioctl<T>(fd: libc::c_int, request: libc::c_ulong, arg: *mut T) -> io::Result<()> {
+    let res = unsafe { libc::ioctl(fd, request as _, arg) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    return Ok(());
+}
+
+fn abs_info(fd: libc::c_int, abs: u16) -> io::Result<libc::input_absinfo> {
+    let mut abs_info: libc::input_absinfo = unsafe { mem::zeroed() };
+    ioctl(fd, eviocgabs(abs), &mut abs_info)?;
+    return Ok(abs_info);
+}
+
+fn abs_range(fd: libc::c_int, abs: u16) -> io::Result<AbsRange> {
+    let abs_info = abs_info(fd, abs)?;
+    return Ok(AbsRange {
+        min: abs_info.minimum,
+        max: abs_info.maximum,
+    });
+}
+
+/// Opens the first multitouch device in /dev/input,
+/// matching `filter` as path or part of the device name if set.
+pub fn find_multitouch_device(filter: Option<&str>) -> Option<EvdevDevice> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir("/dev/input") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .map_or(false, |name| name.starts_with("event"))
+            })
+            .collect(),
+        Err(e) => {
+            debug!("Failed to list /dev/input: {}", e);
+            return None;
+        }
+    };
+    paths.sort();
+    for path in paths {
+        match filter {
+            Some(filter) if filter.starts_with('/') && Path::new(filter) != path => continue,
+            _ => {}
+        }
+        let device = match EvdevDevice::open(&path) {
+            Ok(device) => device,
+            Err(e) => {
+                trace!("[evdev] skipping {:?}: {}", path, e);
+                continue;
+            }
+        };
+        match filter {
+            Some(filter) if !filter.starts_with('/') && !device.name.contains(filter) => continue,
+            _ => {}
+        }
+        return Some(device);
+    }
+    return None;
+}
@@ -0,0 +1,958 @@
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Pad {
+    Left,
+    Right,
+}
+
+/// Swipe direction or touchpad edge, up is positive y.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PinchDirection {
+    In,
+    Out,
+}
+
+/// Gesture as configured, e.g. `{"type": "swipe", "pad": "right", "direction": "left"}`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Gesture {
+    /// touch both touchpads at the same time, release both while visible
+    BothPads,
+    Tap {
+        pad: Pad,
+    },
+    DoubleTap {
+        pad: Pad,
+    },
+    LongPress {
+        pad: Pad,
+    },
+    Swipe {
+        pad: Pad,
+        direction: Direction,
+    },
+    /// swipe from the edge of the touchpad towards the center
+    EdgeSwipe {
+        pad: Pad,
+        edge: Direction,
+    },
+    /// move fingers on both touchpads towards/away from each other
+    Pinch {
+        direction: PinchDirection,
+    },
+}
+
+/// Action run on gesture, e.g. `{"type": "send_key", "key": "backspace"}`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GestureAction {
+    Toggle,
+    /// keep keyboard open until toggled, regardless of close policy
+    ToggleSticky,
+    SwitchLayer {
+        layer: String,
+    },
+    SendKey {
+        key: String,
+    },
+    MoveWindow {
+        x: f64,
+        y: f64,
+    },
+    HapticPattern {
+        name: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GestureBinding {
+    pub gesture: Gesture,
+    pub action: GestureAction,
+}
+
+pub fn default_gesture_bindings() -> Vec<GestureBinding> {
+    return vec![GestureBinding {
+        gesture: Gesture::BothPads,
+        action: GestureAction::Toggle,
+    }];
+}
+
+/// When the keyboard closes without explicitly toggling it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClosePolicy {
+    /// close when both touchpads are released
+    #[default]
+    OnRelease,
+    /// close when the touchpads weren't touched for `close_idle_timeout_ms`
+    IdleTimeout,
+    /// only close by toggle gesture or button
+    Explicit,
+}
+
+/// Times in ms, distances in touchpad units (-32k to +32k).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GestureThresholds {
+    pub tap_ms: u64,
+    pub tap_dist: f32,
+    pub double_tap_ms: u64,
+    pub long_press_ms: u64,
+    pub swipe_ms: u64,
+    pub swipe_dist: f32,
+    /// distance from the center where the edge starts
+    pub edge: i16,
+    pub pinch_dist: f32,
+}
+
+impl Default for GestureThresholds {
+    fn default() -> Self {
+        return Self {
+            tap_ms: 200,
+            tap_dist: 3000.0,
+            double_tap_ms: 400,
+            long_press_ms: 600,
+            swipe_ms: 500,
+            swipe_dist: 10000.0,
+            edge: 24000,
+            pinch_dist: 8000.0,
+        };
+    }
+}
+
+pub struct TouchEntry {
+    pub x: i16,
+    pub y: i16,
+    pub force: u16,
+    pub time: Instant,
+}
+
+impl TouchEntry {
+    pub fn is_touched(&self) -> bool {
+        return self.x != 0 || self.y != 0;
+    }
+}
+
+/// Appends entry and drops entries older than `max_age`.
+pub fn push_touch_entry(
+    touch_history: &mut VecDeque<TouchEntry>,
+    entry: TouchEntry,
+    max_age: Duration,
+) {
+    let now = entry.time;
+    touch_history.push_back(entry);
+    loop {
+        match touch_history.front() {
+            Some(front) if now.duration_since(front.time) > max_age => {
+                touch_history.pop_front();
+            }
+            _ => break,
+        }
+    }
+}
+
+/// Touched entries of a touchpad without release in between.
+struct Stroke<'a> {
+    start: &'a TouchEntry,
+    end: &'a TouchEntry,
+    /// released with the latest entry
+    released: bool,
+}
+
+impl Stroke<'_> {
+    fn duration(&self) -> Duration {
+        return self.end.time.duration_since(self.start.time);
+    }
+
+    fn delta(&self) -> (f32, f32) {
+        return (
+            f32::from(self.end.x) - f32::from(self.start.x),
+            f32::from(self.end.y) - f32::from(self.start.y),
+        );
+    }
+
+    fn dist(&self) -> f32 {
+        let (x, y) = self.delta();
+        return (x * x + y * y).sqrt();
+    }
+
+    fn direction(&self) -> Direction {
+        let (x, y) = self.delta();
+        if x.abs() > y.abs() {
+            return if x > 0.0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            };
+        }
+        return if y > 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        };
+    }
+
+    fn start_edge(&self, edge: i16) -> Option<Direction> {
+        if self.start.x >= edge {
+            return Some(Direction::Right);
+        }
+        if self.start.x <= -edge {
+            return Some(Direction::Left);
+        }
+        if self.start.y >= edge {
+            return Some(Direction::Up);
+        }
+        if self.start.y <= -edge {
+            return Some(Direction::Down);
+        }
+        return None;
+    }
+}
+
+fn opposite(direction: Direction) -> Direction {
+    return match direction {
+        Direction::Up => Direction::Down,
+        Direction::Down => Direction::Up,
+        Direction::Left => Direction::Right,
+        Direction::Right => Direction::Left,
+    };
+}
+
+/// Latest stroke which is either still touched or was released with the latest entry.
+fn last_stroke(touch_history: &VecDeque<TouchEntry>) -> Option<Stroke<'_>> {
+    let mut entries = touch_history.iter().rev().peekable();
+    let released = match entries.peek() {
+        Some(last) => !last.is_touched(),
+        None => return None,
+    };
+    if released {
+        entries.next();
+    }
+    let end = match entries.next() {
+        Some(end) if end.is_touched() => end,
+        _ => return None,
+    };
+    let mut start = end;
+    for prev in entries {
+        if !prev.is_touched() {
+            break;
+        }
+        start = prev;
+    }
+    return Some(Stroke {
+        start,
+        end,
+        released,
+    });
+}
+
+/// Recognizes gestures from the touch history of both touchpads.
+pub struct GestureRecognizer {
+    pub thresholds: GestureThresholds,
+    /// max time between touching left and right touchpad for the both pads gesture
+    pub simultaneity: Duration,
+    /// release time of last tap per pad, for double taps
+    last_tap: [Option<Instant>; 2],
+    /// long press recognized per pad, reset on release so it's only recognized once per touch
+    long_pressed: [bool; 2],
+    /// pinch recognized, reset when either pad is released
+    pinched: bool,
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        return Self {
+            thresholds: GestureThresholds::default(),
+            simultaneity: Duration::from_millis(100),
+            last_tap: [None, None],
+            long_pressed: [false, false],
+            pinched: false,
+        };
+    }
+
+    pub fn recognize(
+        &mut self,
+        left_touch_history: &VecDeque<TouchEntry>,
+        right_touch_history: &VecDeque<TouchEntry>,
+        last_toggle_window: Instant,
+        is_visible: bool,
+        close_on_release: bool,
+    ) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        if check_keyboard_toggle(
+            left_touch_history,
+            right_touch_history,
+            last_toggle_window,
+            is_visible,
+            self.simultaneity,
+            close_on_release,
+        ) {
+            gestures.push(Gesture::BothPads);
+        }
+        let left_stroke = last_stroke(left_touch_history);
+        let right_stroke = last_stroke(right_touch_history);
+        for (index, pad, stroke) in [(0, Pad::Left, &left_stroke), (1, Pad::Right, &right_stroke)] {
+            match stroke {
+                Some(stroke) => self.recognize_stroke(index, pad, stroke, &mut gestures),
+                None => {}
+            }
+        }
+        match (&left_stroke, &right_stroke) {
+            (Some(left_stroke), Some(right_stroke)) => {
+                self.recognize_pinch(left_stroke, right_stroke, &mut gestures)
+            }
+            _ => {}
+        }
+        // the history only covers a limited time, so a held stroke's start moves forward
+        let is_left_touched = is_touched(left_touch_history);
+        let is_right_touched = is_touched(right_touch_history);
+        if !is_left_touched {
+            self.long_pressed[0] = false;
+        }
+        if !is_right_touched {
+            self.long_pressed[1] = false;
+        }
+        if !is_left_touched || !is_right_touched {
+            self.pinched = false;
+        }
+        if !gestures.is_empty() {
+            debug!("recognized gestures {:?}", gestures);
+        }
+        return gestures;
+    }
+
+    fn recognize_stroke(
+        &mut self,
+        index: usize,
+        pad: Pad,
+        stroke: &Stroke,
+        gestures: &mut Vec<Gesture>,
+    ) {
+        let thresholds = &self.thresholds;
+        let is_long_pressed = self.long_pressed[index];
+        if !stroke.released {
+            if !is_long_pressed
+                && stroke.duration() >= Duration::from_millis(thresholds.long_press_ms)
+                && stroke.dist() <= thresholds.tap_dist
+            {
+                self.long_pressed[index] = true;
+                gestures.push(Gesture::LongPress { pad });
+            }
+            return;
+        }
+        if is_long_pressed {
+            return;
+        }
+        if stroke.duration() <= Duration::from_millis(thresholds.tap_ms)
+            && stroke.dist() <= thresholds.tap_dist
+        {
+            gestures.push(Gesture::Tap { pad });
+            let is_double_tap = match self.last_tap[index] {
+                Some(last_tap) => {
+                    stroke.start.time.duration_since(last_tap)
+                        <= Duration::from_millis(thresholds.double_tap_ms)
+                }
+                None => false,
+            };
+            if is_double_tap {
+                self.last_tap[index] = None;
+                gestures.push(Gesture::DoubleTap { pad });
+            } else {
+                self.last_tap[index] = Some(stroke.end.time);
+            }
+            return;
+        }
+        if stroke.duration() <= Duration::from_millis(thresholds.swipe_ms)
+            && stroke.dist() >= thresholds.swipe_dist
+        {
+            let direction = stroke.direction();
+            match stroke.start_edge(thresholds.edge) {
+                Some(edge) if opposite(edge) == direction => {
+                    gestures.push(Gesture::EdgeSwipe { pad, edge });
+                }
+                _ => gestures.push(Gesture::Swipe { pad, direction }),
+            }
+        }
+    }
+
+    fn recognize_pinch(
+        &mut self,
+        left_stroke: &Stroke,
+        right_stroke: &Stroke,
+        gestures: &mut Vec<Gesture>,
+    ) {
+        if left_stroke.released || right_stroke.released || self.pinched {
+            return;
+        }
+        // horizontal distance change between fingers, touchpad offset cancels out
+        let start_dist = f32::from(right_stroke.start.x) - f32::from(left_stroke.start.x);
+        let end_dist = f32::from(right_stroke.end.x) - f32::from(left_stroke.end.x);
+        let change = end_dist - start_dist;
+        if change.abs() < self.thresholds.pinch_dist {
+            return;
+        }
+        self.pinched = true;
+        gestures.push(Gesture::Pinch {
+            direction: if change > 0.0 {
+                PinchDirection::Out
+            } else {
+                PinchDirection::In
+            },
+        });
+    }
+}
+
+fn check_keyboard_toggle(
+    left_touch_history: &VecDeque<TouchEntry>,
+    right_touch_history: &VecDeque<TouchEntry>,
+    last_toggle_window: Instant,
+    is_visible: bool,
+    simultaneity: Duration,
+    close_on_release: bool,
+) -> bool {
+    if left_touch_history.len() == 0 || right_touch_history.len() == 0 {
+        trace!("left or right touch history is empty");
+        return false;
+    }
+    let last_left_touch = left_touch_history.back().unwrap();
+    let last_right_touch = right_touch_history.back().unwrap();
+    let is_left_touched = last_left_touch.is_touched();
+    let is_right_touched = last_right_touch.is_touched();
+    // only close if touched since opened, e.g. not when opened by button
+    let is_touched_since_toggle = left_touch_history
+        .iter()
+        .chain(right_touch_history.iter())
+        .any(|touch| touch.time > last_toggle_window && touch.is_touched());
+    if close_on_release
+        && is_visible
+        && is_touched_since_toggle
+        && (!is_left_touched && !is_right_touched)
+    {
+        debug!("Visible and both touchpads released, closing keyboard");
+        return true;
+    }
+    let left_touch_time = get_last_touch_start_time(left_touch_history);
+    let right_touch_time = get_last_touch_start_time(right_touch_history);
+    if left_touch_time.is_none() || right_touch_time.is_none() {
+        trace!("left or right isn't touched");
+        return false;
+    }
+    let left_touch_time = left_touch_time.unwrap();
+    let right_touch_time = right_touch_time.unwrap();
+    // the toggling touch itself starts at the toggle time when it's set to the report time
+    if left_touch_time <= last_toggle_window || right_touch_time <= last_toggle_window {
+        trace!("touch time not after last toggle");
+        return false;
+    }
+    let time_diff = if left_touch_time > right_touch_time {
+        left_touch_time - right_touch_time
+    } else {
+        right_touch_time - left_touch_time
+    };
+    trace!("time difference {}", time_diff.as_millis());
+    if time_diff < simultaneity {
+        trace!("time difference below threshold");
+        return true;
+    }
+    return false;
+}
+
+fn is_touched(touch_history: &VecDeque<TouchEntry>) -> bool {
+    return match touch_history.back() {
+        Some(last) => last.is_touched(),
+        None => false,
+    };
+}
+
+fn get_last_touch_start_time(touch_history: &VecDeque<TouchEntry>) -> Option<Instant> {
+    if touch_history.is_empty() {
+        return None;
+    }
+    let mut curr = touch_history.back().unwrap();
+    for prev in touch_history.iter().rev() {
+        if curr.is_touched() && !prev.is_touched() {
+            return Some(curr.time);
+        }
+        curr = prev;
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Xorshift, so generated histories are reproducible without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            return self.0;
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            return self.next() % n;
+        }
+    }
+
+    fn entry(time: Instant, touched: bool) -> TouchEntry {
+        let (x, y) = if touched { (1200, -3400) } else { (0, 0) };
+        return TouchEntry {
+            x: x,
+            y: y,
+            force: 0,
+            time: time,
+        };
+    }
+
+    /// History of up to `max_len` entries 1 to 20ms apart, starting after `start`.
+    fn random_history(rng: &mut Rng, start: Instant, max_len: u64) -> VecDeque<TouchEntry> {
+        let mut time = start;
+        let mut history = VecDeque::new();
+        for _ in 0..rng.below(max_len + 1) {
+            time += Duration::from_millis(1 + rng.below(20));
+            history.push_back(entry(time, rng.below(2) == 0));
+        }
+        return history;
+    }
+
+    /// Released then touched at `touch_start`, touched until `end`.
+    fn touch_history(start: Instant, touch_start: Instant, end: Instant) -> VecDeque<TouchEntry> {
+        let mut history = VecDeque::from([entry(start, false)]);
+        let mut time = touch_start;
+        while time <= end {
+            history.push_back(entry(time, true));
+            time += Duration::from_millis(4);
+        }
+        return history;
+    }
+
+    /// Time of the last touched entry following a released one, as a forward scan.
+    fn last_touch_start_reference(touch_history: &VecDeque<TouchEntry>) -> Option<Instant> {
+        let mut result = None;
+        for (prev, curr) in touch_history.iter().zip(touch_history.iter().skip(1)) {
+            if !prev.is_touched() && curr.is_touched() {
+                result = Some(curr.time);
+            }
+        }
+        return result;
+    }
+
+    #[test]
+    fn last_touch_start_time_matches_reference() {
+        let mut rng = Rng(0x5eed);
+        let start = Instant::now();
+        for _ in 0..2000 {
+            let history = random_history(&mut rng, start, 24);
+            assert_eq!(
+                get_last_touch_start_time(&history),
+                last_touch_start_reference(&history)
+            );
+        }
+    }
+
+    #[test]
+    fn last_touch_start_time_needs_release() {
+        let start = Instant::now();
+        for len in 0..10 {
+            let history: VecDeque<TouchEntry> = (0..len)
+                .map(|i| entry(start + Duration::from_millis(i), true))
+                .collect();
+            assert_eq!(get_last_touch_start_time(&history), None);
+        }
+    }
+
+    #[test]
+    fn keyboard_toggle_needs_both_histories() {
+        let mut rng = Rng(0xb07);
+        let start = Instant::now();
+        for _ in 0..500 {
+            let history = random_history(&mut rng, start, 24);
+            for (is_visible, close_on_release) in
+                [(false, false), (false, true), (true, false), (true, true)]
+            {
+                let simultaneity = Duration::from_millis(rng.below(500));
+                let empty = VecDeque::new();
+                assert!(!check_keyboard_toggle(
+                    &history,
+                    &empty,
+                    start,
+                    is_visible,
+                    simultaneity,
+                    close_on_release
+                ));
+                assert!(!check_keyboard_toggle(
+                    &empty,
+                    &history,
+                    start,
+                    is_visible,
+                    simultaneity,
+                    close_on_release
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn keyboard_toggle_is_symmetric() {
+        let mut rng = Rng(0x5111);
+        let start = Instant::now();
+        for _ in 0..2000 {
+            let left = random_history(&mut rng, start, 16);
+            let right = random_history(&mut rng, start, 16);
+            let last_toggle_window = start + Duration::from_millis(rng.below(200));
+            let is_visible = rng.below(2) == 0;
+            let simultaneity = Duration::from_millis(rng.below(200));
+            let close_on_release = rng.below(2) == 0;
+            assert_eq!(
+                check_keyboard_toggle(
+                    &left,
+                    &right,
+                    last_toggle_window,
+                    is_visible,
+                    simultaneity,
+                    close_on_release
+                ),
+                check_keyboard_toggle(
+                    &right,
+                    &left,
+                    last_toggle_window,
+                    is_visible,
+                    simultaneity,
+                    close_on_release
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn keyboard_toggle_on_simultaneous_touch() {
+        let mut rng = Rng(0x70c);
+        let start = Instant::now();
+        let last_toggle_window = start;
+        let simultaneity = Duration::from_millis(100);
+        for _ in 0..1000 {
+            let left_start = start + Duration::from_millis(1 + rng.below(300));
+            let right_start = start + Duration::from_millis(1 + rng.below(300));
+            let end = left_start.max(right_start) + Duration::from_millis(rng.below(100));
+            let left = touch_history(start, left_start, end);
+            let right = touch_history(start, right_start, end);
+            let is_simultaneous = if left_start > right_start {
+                left_start - right_start < simultaneity
+            } else {
+                right_start - left_start < simultaneity
+            };
+            for (is_visible, close_on_release) in
+                [(false, false), (false, true), (true, false), (true, true)]
+            {
+                // both pads are still touched, so closing on release doesn't apply
+                assert_eq!(
+                    check_keyboard_toggle(
+                        &left,
+                        &right,
+                        last_toggle_window,
+                        is_visible,
+                        simultaneity,
+                        close_on_release
+                    ),
+                    is_simultaneous
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keyboard_toggle_ignores_touches_before_toggle() {
+        let mut rng = Rng(0xbef0);
+        let start = Instant::now();
+        let simultaneity = Duration::from_millis(100);
+        for _ in 0..1000 {
+            let touch_start = start + Duration::from_millis(1 + rng.below(300));
+            let end = touch_start + Duration::from_millis(rng.below(100));
+            let left = touch_history(start, touch_start, end);
+            let right = touch_history(start, touch_start, end);
+            let last_toggle_window = touch_start + Duration::from_millis(rng.below(100));
+            assert!(!check_keyboard_toggle(
+                &left,
+                &right,
+                last_toggle_window,
+                rng.below(2) == 0,
+                simultaneity,
+                false
+            ));
+        }
+    }
+
+    #[test]
+    fn keyboard_toggle_closes_on_release_once_touched() {
+        let mut rng = Rng(0xc105e);
+        let start = Instant::now();
+        let simultaneity = Duration::from_millis(100);
+        for _ in 0..1000 {
+            let mut left = random_history(&mut rng, start, 16);
+            let mut right = random_history(&mut rng, start, 16);
+            let end = start + Duration::from_millis(1000);
+            left.push_back(entry(end, false));
+            right.push_back(entry(end, false));
+            let last_toggle_window = start + Duration::from_millis(rng.below(400));
+            let is_touched_since_toggle = left
+                .iter()
+                .chain(right.iter())
+                .any(|touch| touch.time > last_toggle_window && touch.is_touched());
+            assert_eq!(
+                check_keyboard_toggle(&left, &right, last_toggle_window, true, simultaneity, true),
+                is_touched_since_toggle
+            );
+        }
+    }
+
+    /// Feeds both touchpads a report every 4ms like the HID thread does, collecting gestures.
+    struct Touchpads {
+        recognizer: GestureRecognizer,
+        left: VecDeque<TouchEntry>,
+        right: VecDeque<TouchEntry>,
+        start: Instant,
+        time: Instant,
+        max_age: Duration,
+        gestures: Vec<Gesture>,
+    }
+
+    type Touch = Option<(i16, i16)>;
+
+    impl Touchpads {
+        fn new() -> Self {
+            let start = Instant::now();
+            return Self {
+                recognizer: GestureRecognizer::new(),
+                left: VecDeque::new(),
+                right: VecDeque::new(),
+                start: start,
+                time: start,
+                max_age: Duration::from_millis(2000),
+                gestures: Vec::new(),
+            };
+        }
+
+        fn step(&mut self, left: Touch, right: Touch) {
+            self.time += Duration::from_millis(4);
+            for (history, touch) in [(&mut self.left, left), (&mut self.right, right)] {
+                let (x, y) = touch.unwrap_or((0, 0));
+                let entry = TouchEntry {
+                    x: x,
+                    y: y,
+                    force: 0,
+                    time: self.time,
+                };
+                push_touch_entry(history, entry, self.max_age);
+            }
+            let gestures =
+                self.recognizer
+                    .recognize(&self.left, &self.right, self.start, false, false);
+            self.gestures.extend(
+                gestures
+                    .into_iter()
+                    .filter(|gesture| *gesture != Gesture::BothPads),
+            );
+        }
+
+        fn hold(&mut self, ms: u64, left: Touch, right: Touch) {
+            for _ in 0..ms / 4 {
+                self.step(left, right);
+            }
+        }
+
+        /// Moves linearly from `from` to `to`, `None` keeps the pad released.
+        fn drag(
+            &mut self,
+            ms: u64,
+            left: Option<((i16, i16), (i16, i16))>,
+            right: Option<((i16, i16), (i16, i16))>,
+        ) {
+            let steps = ms / 4;
+            let lerp = |touch: Option<((i16, i16), (i16, i16))>, step: u64| {
+                return touch.map(|(from, to)| {
+                    let t = step as f32 / (steps - 1) as f32;
+                    let x = f32::from(from.0) + (f32::from(to.0) - f32::from(from.0)) * t;
+                    let y = f32::from(from.1) + (f32::from(to.1) - f32::from(from.1)) * t;
+                    (x as i16, y as i16)
+                });
+            };
+            for step in 0..steps {
+                self.step(lerp(left, step), lerp(right, step));
+            }
+        }
+
+        fn release(&mut self) {
+            self.hold(100, None, None);
+        }
+    }
+
+    const CENTER: Touch = Some((1000, 1000));
+
+    #[test]
+    fn tap() {
+        let mut pads = Touchpads::new();
+        pads.release();
+        pads.hold(100, CENTER, None);
+        pads.release();
+        assert_eq!(pads.gestures, vec![Gesture::Tap { pad: Pad::Left }]);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut pads = Touchpads::new();
+        pads.release();
+        pads.hold(100, None, CENTER);
+        pads.release();
+        pads.hold(100, None, CENTER);
+        pads.release();
+        let tap = Gesture::Tap { pad: Pad::Right };
+        let double_tap = Gesture::DoubleTap { pad: Pad::Right };
+        assert_eq!(pads.gestures, vec![tap.clone(), tap, double_tap]);
+    }
+
+    #[test]
+    fn slow_taps_are_no_double_tap() {
+        let mut pads = Touchpads::new();
+        pads.release();
+        pads.hold(100, CENTER, None);
+        pads.hold(600, None, None);
+        pads.hold(100, CENTER, None);
+        pads.release();
+        let tap = Gesture::Tap { pad: Pad::Left };
+        assert_eq!(pads.gestures, vec![tap.clone(), tap]);
+    }
+
+    #[test]
+    fn long_press_held_longer_than_history() {
+        let mut pads = Touchpads::new();
+        pads.release();
+        pads.hold(3 * pads.max_age.as_millis() as u64, CENTER, None);
+        pads.release();
+        assert_eq!(pads.gestures, vec![Gesture::LongPress { pad: Pad::Left }]);
+        // recognized again after release
+        pads.hold(700, CENTER, None);
+        pads.release();
+        assert_eq!(
+            pads.gestures,
+            vec![
+                Gesture::LongPress { pad: Pad::Left },
+                Gesture::LongPress { pad: Pad::Left }
+            ]
+        );
+    }
+
+    #[test]
+    fn swipe_direction() {
+        for (from, to, direction) in [
+            ((-8000, 0), (8000, 0), Direction::Right),
+            ((8000, 0), (-8000, 0), Direction::Left),
+            ((0, -8000), (0, 8000), Direction::Up),
+            ((0, 8000), (0, -8000), Direction::Down),
+        ] {
+            let mut pads = Touchpads::new();
+            pads.release();
+            pads.drag(200, None, Some((from, to)));
+            pads.release();
+            assert_eq!(
+                pads.gestures,
+                vec![Gesture::Swipe {
+                    pad: Pad::Right,
+                    direction: direction
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn slow_swipe_is_ignored() {
+        let mut pads = Touchpads::new();
+        pads.release();
+        pads.drag(1000, Some(((-8000, 0), (8000, 0))), None);
+        pads.release();
+        assert_eq!(pads.gestures, vec![]);
+    }
+
+    #[test]
+    fn edge_swipe() {
+        for (from, to, edge) in [
+            ((30000, 0), (10000, 0), Direction::Right),
+            ((-30000, 0), (-10000, 0), Direction::Left),
+            ((0, 30000), (0, 10000), Direction::Up),
+            ((0, -30000), (0, -10000), Direction::Down),
+        ] {
+            let mut pads = Touchpads::new();
+            pads.release();
+            pads.drag(200, Some((from, to)), None);
+            pads.release();
+            assert_eq!(
+                pads.gestures,
+                vec![Gesture::EdgeSwipe {
+                    pad: Pad::Left,
+                    edge: edge
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn swipe_along_edge_is_no_edge_swipe() {
+        let mut pads = Touchpads::new();
+        pads.release();
+        pads.drag(200, Some(((30000, -8000), (30000, 8000))), None);
+        pads.release();
+        assert_eq!(
+            pads.gestures,
+            vec![Gesture::Swipe {
+                pad: Pad::Left,
+                direction: Direction::Up
+            }]
+        );
+    }
+
+    #[test]
+    fn pinch() {
+        for (left, right, direction) in [
+            (((-2000, 0), (-8000, 0)), ((2000, 0), (8000, 0)), PinchDirection::Out),
+            (((-8000, 0), (-2000, 0)), ((8000, 0), (2000, 0)), PinchDirection::In),
+        ] {
+            let mut pads = Touchpads::new();
+            pads.release();
+            pads.drag(1000, Some(left), Some(right));
+            // keep holding past the history length, still recognized once
+            pads.hold(3000, Some(left.1), Some(right.1));
+            pads.release();
+            let pinches: Vec<&Gesture> = pads
+                .gestures
+                .iter()
+                .filter(|gesture| matches!(gesture, Gesture::Pinch { .. }))
+                .collect();
+            assert_eq!(
+                pinches,
+                vec![&Gesture::Pinch {
+                    direction: direction
+                }]
+            );
+        }
+    }
+}
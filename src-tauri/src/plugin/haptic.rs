@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Haptic pulse parameters as sent with ID_TRIGGER_HAPTIC_PULSE.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct HapticPulse {
+    pub duration: u16,
+    pub interval: u16,
+    pub count: u16,
+    pub gain: u8,
+}
+
+impl Default for HapticPulse {
+    fn default() -> Self {
+        return Self {
+            duration: 0xffff,
+            interval: 0,
+            count: 1,
+            gain: 0xff,
+        };
+    }
+}
+
+/// Step of a haptic pattern, e.g. `{"pad": 1, "delay_ms": 80, "duration": 8000}`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HapticStep {
+    /// 0 = right, 1 = left, 2 = both
+    #[serde(default = "both_pads")]
+    pub pad: u8,
+    /// delay after the previous step
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(flatten)]
+    pub pulse: HapticPulse,
+}
+
+fn both_pads() -> u8 {
+    return 2;
+}
+
+#[derive(Debug, Clone)]
+pub enum HapticCommand {
+    /// pad: 0 = right, 1 = left, 2 = both
+    Pulse {
+        pad: u8,
+        pulse: HapticPulse,
+    },
+    Preset {
+        pad: u8,
+        name: String,
+    },
+    /// play named pattern, after the currently playing one if queued
+    Pattern {
+        name: String,
+        queue: bool,
+    },
+    Steps {
+        steps: Vec<HapticStep>,
+        queue: bool,
+    },
+    /// cancel playing and queued patterns
+    Cancel,
+    /// rumble motor speeds, stopped after duration
+    Rumble(Rumble),
+}
+
+/// Rumble motor command, e.g. `{"left": 20000, "right": 20000, "duration_ms": 200}`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Rumble {
+    pub left: u16,
+    pub right: u16,
+    pub duration_ms: u64,
+}
+
+/// Presets used if not overridden by `haptic_presets` in the config.
+pub fn default_haptic_presets() -> HashMap<String, HapticPulse> {
+    return HashMap::from([
+        ("hover".to_string(), HapticPulse::default()),
+        ("key_press".to_string(), HapticPulse::default()),
+        (
+            "layer_switch".to_string(),
+            HapticPulse {
+                duration: 0x8000,
+                ..Default::default()
+            },
+        ),
+        (
+            "error".to_string(),
+            HapticPulse {
+                duration: 0x8000,
+                interval: 0x8000,
+                count: 3,
+                ..Default::default()
+            },
+        ),
+        (
+            "toggle".to_string(),
+            HapticPulse {
+                duration: 0x4000,
+                ..Default::default()
+            },
+        ),
+    ]);
+}
+
+/// Patterns used if not overridden by `haptic_patterns` in the config.
+pub fn default_haptic_patterns() -> HashMap<String, Vec<HapticStep>> {
+    let step = |delay_ms: u64, duration: u16| HapticStep {
+        pad: both_pads(),
+        delay_ms: delay_ms,
+        pulse: HapticPulse {
+            duration: duration,
+            ..Default::default()
+        },
+    };
+    return HashMap::from([
+        (
+            "modifier_lock".to_string(),
+            vec![step(0, 0x6000), step(80, 0x6000)],
+        ),
+        (
+            "long_press".to_string(),
+            vec![
+                step(0, 0x1000),
+                step(40, 0x2000),
+                step(40, 0x4000),
+                step(40, 0x8000),
+                step(40, 0xffff),
+            ],
+        ),
+    ]);
+}
+
+/// Plays haptic patterns by returning their pulses once due.
+pub struct HapticSequencer {
+    pending: VecDeque<(Instant, u8, HapticPulse)>,
+}
+
+impl HapticSequencer {
+    pub fn new() -> Self {
+        return Self {
+            pending: VecDeque::new(),
+        };
+    }
+
+    pub fn play(&mut self, steps: &[HapticStep], queue: bool, now: Instant) {
+        if !queue {
+            self.pending.clear();
+        }
+        let mut time = match self.pending.back() {
+            Some((last_time, _, _)) => *last_time.max(&now),
+            None => now,
+        };
+        for step in steps {
+            time += Duration::from_millis(step.delay_ms);
+            self.pending.push_back((time, step.pad, step.pulse));
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Time the next step is due.
+    pub fn next_due(&self) -> Option<Instant> {
+        return self.pending.front().map(|(time, _, _)| *time);
+    }
+
+    /// Removes and returns pad and pulse of all steps due at `now`.
+    pub fn due(&mut self, now: Instant) -> Vec<(u8, HapticPulse)> {
+        let mut result = Vec::new();
+        loop {
+            match self.pending.front() {
+                Some((time, pad, pulse)) if *time <= now => {
+                    result.push((*pad, *pulse));
+                    self.pending.pop_front();
+                }
+                _ => break,
+            }
+        }
+        return result;
+    }
+}
@@ -0,0 +1,61 @@
+use hidapi::HidDevice;
+use log::{debug, trace};
+use std::{future, thread, time::Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// Result of reading the hid device on the reader thread.
+pub enum HidRead {
+    /// report with the time it was read
+    Report { buf: Vec<u8>, time: Instant },
+    /// reading failed, the reader thread stopped
+    Failed(String),
+    /// all reports of a recording were replayed
+    End,
+}
+
+/// Reads reports of up to `report_size` bytes on a separate thread,
+/// so the plugin loop only wakes up for reports and commands.
+/// The thread stops once reading fails or the receiver is dropped.
+pub fn spawn_hid_reader(device: HidDevice, report_size: usize) -> UnboundedReceiver<HidRead> {
+    let (reports_tx, reports_rx) = unbounded_channel::<HidRead>();
+    thread::spawn(move || {
+        let mut buf = vec![0u8; report_size];
+        loop {
+            // timeout to notice a dropped receiver without reports
+            match device.read_timeout(&mut buf[..], 100) {
+                Ok(0) => {
+                    if reports_tx.is_closed() {
+                        break;
+                    }
+                }
+                Ok(res) => {
+                    let report = HidRead::Report {
+                        buf: buf[..res].to_vec(),
+                        time: Instant::now(),
+                    };
+                    if reports_tx.send(report).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    trace!("[HID reader] read failed: {}", e);
+                    let _ = reports_tx.send(HidRead::Failed(e.to_string()));
+                    break;
+                }
+            }
+        }
+        debug!("[HID reader] stopped");
+    });
+    return reports_rx;
+}
+
+/// Waits for the next read, never returns without reader.
+pub async fn recv_hid_read(reader: &mut Option<UnboundedReceiver<HidRead>>) -> HidRead {
+    return match reader {
+        Some(reader) => match reader.recv().await {
+            Some(hid_read) => hid_read,
+            None => HidRead::Failed("reader stopped".to_string()),
+        },
+        None => future::pending().await,
+    };
+}
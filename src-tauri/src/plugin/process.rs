@@ -0,0 +1,661 @@
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+    },
+    panic,
+    path::PathBuf,
+    sync::{Mutex, TryLockError},
+    task::{Context, Poll},
+};
+use tokio::io::unix::AsyncFd;
+
+/// Matches processes by their /proc entries, see `find_processes`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessMatcher {
+    /// glob of the executable path, `*` matches any characters and `?` one
+    Exe(String),
+    /// command name, truncated to 15 characters by the kernel
+    Comm(String),
+    /// substring of the command line, arguments separated by spaces
+    Cmdline(String),
+    /// substring of the cgroup, e.g. a flatpak app id
+    Cgroup(String),
+}
+
+impl fmt::Display for ProcessMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ProcessMatcher::Exe(pattern) => write!(f, "exe {}", pattern),
+            ProcessMatcher::Comm(comm) => write!(f, "comm {}", comm),
+            ProcessMatcher::Cmdline(cmdline) => write!(f, "cmdline {}", cmdline),
+            ProcessMatcher::Cgroup(cgroup) => write!(f, "cgroup {}", cgroup),
+        };
+    }
+}
+
+/// Steam client of a regular or flatpak install (any user, SteamOS, Bazzite, ChimeraOS),
+/// then anything called steam.
+/// Not the flatpak cgroup, games launched by steam run in the same cgroup.
+pub fn default_steam_matchers() -> Vec<ProcessMatcher> {
+    return vec![
+        ProcessMatcher::Exe("*/ubuntu12_32/steam".to_string()),
+        ProcessMatcher::Comm("steam".to_string()),
+    ];
+}
+
+/// Signals usable to suspend and resume processes.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Signal {
+    Sigstop,
+    Sigcont,
+    Sigtstp,
+    Sighup,
+    Sigint,
+    Sigterm,
+    Sigusr1,
+    Sigusr2,
+}
+
+impl Signal {
+    pub fn number(self) -> i32 {
+        return match self {
+            Signal::Sigstop => libc::SIGSTOP,
+            Signal::Sigcont => libc::SIGCONT,
+            Signal::Sigtstp => libc::SIGTSTP,
+            Signal::Sighup => libc::SIGHUP,
+            Signal::Sigint => libc::SIGINT,
+            Signal::Sigterm => libc::SIGTERM,
+            Signal::Sigusr1 => libc::SIGUSR1,
+            Signal::Sigusr2 => libc::SIGUSR2,
+        };
+    }
+
+    fn from_number(number: i32) -> Option<Self> {
+        return [
+            Signal::Sigstop,
+            Signal::Sigcont,
+            Signal::Sigtstp,
+            Signal::Sighup,
+            Signal::Sigint,
+            Signal::Sigterm,
+            Signal::Sigusr1,
+            Signal::Sigusr2,
+        ]
+        .into_iter()
+        .find(|signal| signal.number() == number);
+    }
+}
+
+/// Suspended processes as (pid, start time, resume signal),
+/// the start time avoids resuming a reused pid.
+static SUSPENDED: Mutex<Vec<(i32, u64, Signal)>> = Mutex::new(Vec::new());
+
+/// Handle of a process which, unlike its pid, can't refer to another process
+/// once it exited.
+pub struct Pidfd {
+    pid: i32,
+    start_time: u64,
+    /// registered with the runtime on the first `poll_exited`, dropped before `fd`
+    watch: Option<AsyncFd<RawFd>>,
+    fd: OwnedFd,
+}
+
+impl Pidfd {
+    pub fn open(pid: i32) -> io::Result<Self> {
+        let fd = pidfd_open(pid);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let start_time = process_start_time(pid);
+        let pidfd = Self {
+            pid: pid,
+            start_time: start_time.unwrap_or(0),
+            watch: None,
+            fd: fd,
+        };
+        // the start time was read from the same process if it's still alive afterwards
+        if start_time.is_none() || !pidfd.is_alive() {
+            return Err(io::Error::from_raw_os_error(libc::ESRCH));
+        }
+        return Ok(pidfd);
+    }
+
+    pub fn pid(&self) -> i32 {
+        return self.pid;
+    }
+
+    /// The pidfd becomes readable once the process exits.
+    pub fn is_alive(&self) -> bool {
+        let mut poll_fd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        return unsafe { libc::poll(&mut poll_fd, 1, 0) } == 0;
+    }
+
+    pub fn send_signal(&self, signal: i32) -> io::Result<()> {
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                self.fd.as_raw_fd(),
+                signal,
+                std::ptr::null::<libc::siginfo_t>(),
+                0,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    /// Ready once the process exited, must be polled within a tokio runtime.
+    pub fn poll_exited(&mut self, cx: &mut Context) -> Poll<()> {
+        if self.watch.is_none() {
+            match AsyncFd::with_interest(self.fd.as_raw_fd(), tokio::io::Interest::READABLE) {
+                Ok(watch) => self.watch = Some(watch),
+                Err(e) => {
+                    error!("Failed to watch pid {}: {}", self.pid, e);
+                    return Poll::Pending;
+                }
+            }
+        }
+        return match self.watch.as_ref().unwrap().poll_read_ready(cx) {
+            Poll::Ready(Ok(_)) => Poll::Ready(()),
+            Poll::Ready(Err(_)) | Poll::Pending => Poll::Pending,
+        };
+    }
+}
+
+/// Suspends a process with `signal` and records it with its `resume` signal,
+/// so it's resumed even if we crash.
+pub fn suspend_process(process: &Pidfd, signal: Signal, resume: Signal) {
+    {
+        let mut suspended = SUSPENDED.lock().unwrap_or_else(|e| e.into_inner());
+        if !suspended
+            .iter()
+            .any(|entry| entry.0 == process.pid && entry.1 == process.start_time)
+        {
+            suspended.push((process.pid, process.start_time, resume));
+        }
+        // record before suspending, the watcher can only resume what's in the file
+        write_state_file(&suspended);
+    }
+    debug!("Sending {:?} to pid {}", signal, process.pid);
+    match process.send_signal(signal.number()) {
+        Ok(_) => {}
+        Err(e) => warn!("Failed to signal pid {}: {}", process.pid, e),
+    }
+}
+
+/// Resumes a process with `resume` and forgets it, also if it already exited.
+pub fn resume_process(process: &Pidfd, resume: Signal) {
+    debug!("Sending {:?} to pid {}", resume, process.pid);
+    match process.send_signal(resume.number()) {
+        Ok(_) => {}
+        Err(e) => debug!("Failed to signal pid {}: {}", process.pid, e),
+    }
+    let mut suspended = SUSPENDED.lock().unwrap_or_else(|e| e.into_inner());
+    let len = suspended.len();
+    suspended.retain(|entry| !(entry.0 == process.pid && entry.1 == process.start_time));
+    if suspended.len() != len {
+        write_state_file(&suspended);
+    }
+}
+
+/// Resumes every process suspended by a previous run, then makes sure the processes
+/// suspended by this run are resumed when it panics or dies, by a panic hook
+/// and a forked watcher process.
+/// Call before any other thread is started, forking only copies the calling thread.
+pub fn install_resume_guard() {
+    resume_from_state_file();
+    spawn_resume_watcher();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // the panicking thread may hold the lock, locking again would deadlock
+        let mut suspended = match SUSPENDED.try_lock() {
+            Ok(suspended) => suspended,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                resume_from_state_file();
+                default_hook(info);
+                return;
+            }
+        };
+        // no pidfds here, the start time makes sure the pid wasn't reused
+        for (pid, start_time, resume) in suspended.drain(..) {
+            if process_start_time(pid) == Some(start_time) {
+                unsafe {
+                    libc::kill(pid, resume.number());
+                }
+            }
+        }
+        write_state_file(&suspended);
+        default_hook(info);
+    }));
+}
+
+/// `$XDG_RUNTIME_DIR/steamdeck-keyboard/stopped-pids`, one "pid start_time resume_signal" per line.
+/// None without runtime dir, a shared dir like /tmp would let other users plant entries.
+fn state_file_path() -> Option<PathBuf> {
+    let mut path = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+    path.push("steamdeck-keyboard");
+    path.push("stopped-pids");
+    return Some(path);
+}
+
+fn write_state_file(suspended: &[(i32, u64, Signal)]) {
+    let path = match state_file_path() {
+        Some(path) => path,
+        None => {
+            debug!("XDG_RUNTIME_DIR not set, suspended pids aren't persisted");
+            return;
+        }
+    };
+    if suspended.is_empty() {
+        let _ = fs::remove_file(&path);
+        return;
+    }
+    let content: String = suspended
+        .iter()
+        .map(|(pid, start_time, resume)| format!("{} {} {}\n", pid, start_time, resume.number()))
+        .collect();
+    let result = match path.parent() {
+        Some(dir) => fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir),
+        None => Ok(()),
+    }
+    .and_then(|_| {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path)?;
+        // the mode only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(content.as_bytes())
+    });
+    match result {
+        Ok(_) => {}
+        Err(e) => error!("Failed to write {}: {}", path.display(), e),
+    }
+}
+
+/// Content of the state file if it's a regular file owned by us with mode 0600.
+fn read_state_file(path: &PathBuf) -> Option<String> {
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+    let metadata = file.metadata().ok()?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_file() || metadata.uid() != uid || metadata.mode() & 0o777 != 0o600 {
+        warn!(
+            "Ignoring {}, not a file owned by uid {} with mode 0600",
+            path.display(),
+            uid
+        );
+        return None;
+    }
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    return Some(content);
+}
+
+/// Resumes the processes in the state file that are still the same process, removes the file.
+fn resume_from_state_file() {
+    let path = match state_file_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let content = match read_state_file(&path) {
+        Some(content) => content,
+        None => return,
+    };
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let pid = fields.next().and_then(|pid| pid.parse::<i32>().ok());
+        let start_time = fields.next().and_then(|time| time.parse::<u64>().ok());
+        // only signals a rule could have configured, never e.g. SIGKILL
+        let resume = fields
+            .next()
+            .and_then(|resume| resume.parse::<i32>().ok())
+            .and_then(Signal::from_number);
+        match (pid, start_time, resume) {
+            (Some(pid), Some(start_time), Some(resume))
+                if process_start_time(pid) == Some(start_time) =>
+            {
+                info!("Resuming pid {} suspended by a previous run", pid);
+                unsafe {
+                    libc::kill(pid, resume.number());
+                }
+            }
+            _ => debug!("Skipping stale suspended pid entry {:?}", line),
+        }
+    }
+    let _ = fs::remove_file(&path);
+}
+
+/// Finds processes with the first matcher matching any, returns that matcher
+/// and all processes it matched, with their descendants if `include_children`.
+/// This process and its children are never matched.
+pub fn find_processes(
+    matchers: &[ProcessMatcher],
+    include_children: bool,
+) -> Option<(ProcessMatcher, Vec<i32>)> {
+    let own_pid = unsafe { libc::getpid() };
+    let parents: HashMap<i32, i32> = list_pids()
+        .into_iter()
+        .filter_map(|pid| parent_pid(pid).map(|parent| (pid, parent)))
+        .collect();
+    for matcher in matchers {
+        let mut pids: Vec<i32> = parents
+            .iter()
+            .filter(|(pid, parent)| **pid != own_pid && **parent != own_pid)
+            .map(|(pid, _)| *pid)
+            .filter(|pid| process_matches(matcher, *pid))
+            .collect();
+        if pids.is_empty() {
+            continue;
+        }
+        if include_children {
+            // children are appended, so their children are visited too
+            let mut i = 0;
+            while i < pids.len() {
+                let parent = pids[i];
+                for (pid, _) in parents.iter().filter(|(_, ppid)| **ppid == parent) {
+                    if !pids.contains(pid) {
+                        pids.push(*pid);
+                    }
+                }
+                i += 1;
+            }
+        }
+        pids.sort();
+        return Some((matcher.clone(), pids));
+    }
+    return None;
+}
+
+fn process_matches(matcher: &ProcessMatcher, pid: i32) -> bool {
+    return matches(matcher, |file| read_proc_file(pid, file));
+}
+
+/// Matches against the /proc entries returned by `read_file`, e.g. "comm".
+fn matches(matcher: &ProcessMatcher, read_file: impl Fn(&str) -> Option<String>) -> bool {
+    return match matcher {
+        ProcessMatcher::Exe(pattern) => match read_file("exe") {
+            Some(exe) => glob_match(pattern, &exe),
+            None => false,
+        },
+        ProcessMatcher::Comm(comm) => match read_file("comm") {
+            Some(proc_comm) => proc_comm.trim_end() == comm,
+            None => false,
+        },
+        ProcessMatcher::Cmdline(cmdline) => match read_file("cmdline") {
+            Some(proc_cmdline) => proc_cmdline.replace('\0', " ").contains(cmdline.as_str()),
+            None => false,
+        },
+        ProcessMatcher::Cgroup(cgroup) => match read_file("cgroup") {
+            Some(proc_cgroup) => proc_cgroup.contains(cgroup.as_str()),
+            None => false,
+        },
+    };
+}
+
+/// Reads /proc/<pid>/<file>, the link target for exe.
+fn read_proc_file(pid: i32, file: &str) -> Option<String> {
+    let path = format!("/proc/{}/{}", pid, file);
+    if file == "exe" {
+        return Some(fs::read_link(path).ok()?.to_string_lossy().into_owned());
+    }
+    return Some(String::from_utf8_lossy(&fs::read(path).ok()?).into_owned());
+}
+
+/// Matches `*` as any characters and `?` as one character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // pattern position after the last `*` and the text position it matches up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, t));
+        } else {
+            match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            }
+        }
+    }
+    return pattern[p..].iter().all(|c| *c == '*');
+}
+
+fn list_pids() -> Vec<i32> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to read /proc: {}", e);
+            return Vec::new();
+        }
+    };
+    return entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+        .collect();
+}
+
+/// Parent pid, field 4 of /proc/<pid>/stat.
+fn parent_pid(pid: i32) -> Option<i32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    return after_comm.split_whitespace().nth(1)?.parse::<i32>().ok();
+}
+
+/// Start time of the process in clock ticks since boot, field 22 of /proc/<pid>/stat.
+fn process_start_time(pid: i32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the command name may contain spaces, fields are counted after its closing paren
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    return after_comm.split_whitespace().nth(19)?.parse::<u64>().ok();
+}
+
+/// Forks a process which waits for this process to exit, then resumes
+/// everything left in the state file.
+fn spawn_resume_watcher() {
+    let parent = unsafe { libc::getpid() };
+    let pidfd = pidfd_open(parent);
+    let child = unsafe { libc::fork() };
+    if child < 0 {
+        error!("Failed to fork resume watcher");
+    }
+    if child != 0 {
+        if pidfd >= 0 {
+            unsafe {
+                libc::close(pidfd);
+            }
+        }
+        return;
+    }
+    // watcher, stopping the app (e.g. systemd) shouldn't stop it before the app is gone
+    unsafe {
+        libc::signal(libc::SIGTERM, libc::SIG_IGN);
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGHUP, libc::SIG_IGN);
+        libc::prctl(libc::PR_SET_NAME, c"resume-watcher".as_ptr());
+    }
+    if pidfd >= 0 {
+        // the pidfd becomes readable once the process exits
+        let mut poll_fd = libc::pollfd {
+            fd: pidfd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        while unsafe { libc::poll(&mut poll_fd, 1, -1) } < 0 {}
+    } else {
+        // kernels without pidfd, we get reparented once the app exits
+        while unsafe { libc::getppid() } == parent {
+            unsafe {
+                libc::sleep(1);
+            }
+        }
+    }
+    resume_from_state_file();
+    unsafe {
+        libc::_exit(0);
+    }
+}
+
+/// Opens a pidfd, -1 if the kernel doesn't support it.
+fn pidfd_open(pid: i32) -> i32 {
+    return unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) } as i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_steam_paths() {
+        let pattern = "*/ubuntu12_32/steam";
+        assert!(glob_match(
+            pattern,
+            "/home/deck/.local/share/Steam/ubuntu12_32/steam"
+        ));
+        assert!(glob_match(
+            pattern,
+            "/home/gamer/.var/app/com.valvesoftware.Steam/data/Steam/ubuntu12_32/steam"
+        ));
+        assert!(!glob_match(
+            pattern,
+            "/home/deck/.local/share/Steam/ubuntu12_32/steamwebhelper"
+        ));
+        assert!(!glob_match(pattern, "/usr/bin/steam"));
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(glob_match("/usr/*/steam*", "/usr/lib/steam/steam.sh"));
+    }
+
+    #[test]
+    fn never_finds_own_process() {
+        let own_pid = unsafe { libc::getpid() };
+        let own_comm = fs::read_to_string(format!("/proc/{}/comm", own_pid)).unwrap();
+        let matchers = [ProcessMatcher::Comm(own_comm.trim_end().to_string())];
+        match find_processes(&matchers, true) {
+            Some((_, pids)) => assert!(!pids.contains(&own_pid)),
+            None => {}
+        }
+    }
+
+    #[test]
+    fn pidfd_signals_and_watches_exit() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let mut process = Pidfd::open(child.id() as i32).unwrap();
+        assert!(process.is_alive());
+        process.send_signal(libc::SIGKILL).unwrap();
+        // on the runtime plugins run on
+        super::super::block_on(std::future::poll_fn(|cx| process.poll_exited(cx)));
+        assert!(!process.is_alive());
+        child.wait().unwrap();
+        // the pid may be reused now, the pidfd still refers to the exited process
+        assert!(process.send_signal(libc::SIGCONT).is_err());
+    }
+
+    /// /proc entries of a flatpak steam process, the exe as seen from the host.
+    fn flatpak_process(exe: &str, comm: &str) -> HashMap<&'static str, String> {
+        let cgroup = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/\
+                      app-flatpak-com.valvesoftware.Steam-2143.scope\n";
+        return HashMap::from([
+            ("exe", exe.to_string()),
+            ("comm", format!("{}\n", comm)),
+            ("cmdline", format!("{}\0", exe)),
+            ("cgroup", cgroup.to_string()),
+        ]);
+    }
+
+    #[test]
+    fn default_matchers_skip_games_in_flatpak_steam_cgroup() {
+        let steam = flatpak_process(
+            "/home/deck/.var/app/com.valvesoftware.Steam/.local/share/Steam/ubuntu12_32/steam",
+            "steam",
+        );
+        let game = flatpak_process(
+            "/home/deck/.var/app/com.valvesoftware.Steam/.local/share/Steam/steamapps/common/Celeste/Celeste.bin.x86_64",
+            "Celeste.bin.x86",
+        );
+        let matchers = default_steam_matchers();
+        assert!(matchers
+            .iter()
+            .any(|matcher| matches(matcher, |file| steam.get(file).cloned())));
+        for matcher in &matchers {
+            assert!(!matches(matcher, |file| game.get(file).cloned()));
+        }
+    }
+
+    #[test]
+    fn state_file_needs_mode_0600() {
+        let path = env::temp_dir().join(format!("stopped-pids-test-{}", std::process::id()));
+        fs::write(&path, "1 2 18\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(read_state_file(&path), None);
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(read_state_file(&path), Some("1 2 18\n".to_string()));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn signal_numbers_round_trip() {
+        for signal in [Signal::Sigstop, Signal::Sigcont, Signal::Sigusr2] {
+            assert_eq!(Signal::from_number(signal.number()), Some(signal));
+        }
+        assert_eq!(Signal::from_number(libc::SIGKILL), None);
+        assert_eq!(Signal::from_number(0), None);
+    }
+
+    #[test]
+    fn matcher_config_format() {
+        let matchers: Vec<ProcessMatcher> =
+            serde_json::from_str(r#"[{"exe": "*/steam"}, {"cgroup": "steam"}]"#).unwrap();
+        assert_eq!(
+            matchers,
+            vec![
+                ProcessMatcher::Exe("*/steam".to_string()),
+                ProcessMatcher::Cgroup("steam".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use hidapi::HidDevice;
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::gesture::ClosePolicy;
+use super::hid::{recv_hid_read, spawn_hid_reader, HidRead};
+use super::touch::{Reconnect, TouchConfig, TouchPipeline};
+use super::{
+    block_on, send_event, sleep_until, HapticCommand, Plugin, PluginCommand, PluginError,
+    PluginEvent, Rumble,
+};
+
+mod report;
+
+use report::{SonyModel, SONY_DEVICES, SONY_VID};
+
+pub const NAME: &str = "sony";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct Config {
+    #[serde(flatten)]
+    touch: TouchConfig,
+}
+
+/// DualSense and DualShock 4 touchpad,
+/// the left and right half of the touchpad are used as left and right pad.
+pub struct SonyPlugin {
+    config: Config,
+    touch: TouchPipeline,
+    device: Option<HidDevice>,
+    /// reports read by the hid reader thread
+    reports: Option<UnboundedReceiver<HidRead>>,
+    model: SonyModel,
+    device_name: String,
+    is_bluetooth: bool,
+    reconnect: Reconnect,
+    rumble_stop: Option<Instant>,
+}
+
+impl SonyPlugin {
+    pub fn new() -> Self {
+        return Self {
+            config: Config::default(),
+            touch: TouchPipeline::new(),
+            device: None,
+            reports: None,
+            model: SonyModel::DualSense,
+            device_name: String::new(),
+            is_bluetooth: false,
+            reconnect: Reconnect::new(),
+            rumble_stop: None,
+        };
+    }
+}
+
+impl Plugin for SonyPlugin {
+    fn run(
+        &mut self,
+        commands: UnboundedReceiver<PluginCommand>,
+        events: mpsc::Sender<PluginEvent>,
+    ) -> Result<(), PluginError> {
+        return block_on(plugin_loop(self, commands, &events));
+    }
+}
+
+/// Handles commands, hid reports and timers as soon as they arrive,
+/// returns once stopped.
+async fn plugin_loop(
+    plugin: &mut SonyPlugin,
+    mut commands: UnboundedReceiver<PluginCommand>,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    loop {
+        let reconnect_deadline = match plugin.device {
+            Some(_) => None,
+            None => Some(plugin.reconnect.deadline()),
+        };
+        let deadline = [plugin.rumble_stop, reconnect_deadline]
+            .into_iter()
+            .flatten()
+            .min();
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(PluginCommand::Stop) | None => {
+                    if plugin.rumble_stop.take().is_some() {
+                        send_rumble(plugin, 0, 0);
+                    }
+                    return Ok(());
+                }
+                Some(command) => handle_command(plugin, command),
+            },
+            hid_read = recv_hid_read(&mut plugin.reports) => match hid_read {
+                HidRead::Report { buf, time } => handle_report(plugin, events, &buf, time)?,
+                HidRead::Failed(e) => {
+                    warn!("Failed to read device, reconnecting: {}", e);
+                    plugin.device = None;
+                    plugin.reports = None;
+                    plugin.reconnect.reset();
+                    send_event(
+                        events,
+                        PluginEvent::DeviceDisconnected(plugin.device_name.clone()),
+                    )?;
+                }
+                // only sent by replays
+                HidRead::End => {}
+            },
+            _ = sleep_until(deadline) => {}
+        }
+        let now = Instant::now();
+        match plugin.rumble_stop {
+            Some(rumble_stop) if rumble_stop <= now => {
+                plugin.rumble_stop = None;
+                send_rumble(plugin, 0, 0);
+            }
+            _ => {}
+        }
+        if plugin.device.is_none() && plugin.reconnect.is_due(now) {
+            reconnect(plugin, events)?;
+        }
+    }
+}
+
+fn handle_command(plugin: &mut SonyPlugin, command: PluginCommand) {
+    match command {
+        PluginCommand::Pause(message_pause) => {
+            debug!("[HID thread] new pause flag: {}", message_pause);
+            plugin.touch.pause_update(message_pause);
+        }
+        PluginCommand::Config(config_str) => config_update(plugin, config_str),
+        // only rumble is supported
+        PluginCommand::Haptic(HapticCommand::Rumble(rumble)) => start_rumble(plugin, &rumble),
+        PluginCommand::Haptic(command) => {
+            trace!("[HID thread] haptic not supported: {:?}", command)
+        }
+        // handled by the plugin loop
+        PluginCommand::Stop => {}
+    }
+}
+
+fn handle_report(
+    plugin: &mut SonyPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+    buf: &[u8],
+    now: Instant,
+) -> Result<(), PluginError> {
+    let mut device_report = match plugin.model.decode(buf) {
+        Some(device_report) => device_report,
+        None => {
+            trace!("[HID thread] skipping report without touchpad data");
+            return Ok(());
+        }
+    };
+    device_report.source = NAME;
+    plugin.is_bluetooth = plugin.model.is_bluetooth(buf).unwrap_or(false);
+    if plugin.touch.skip_paused(now) {
+        return Ok(());
+    }
+    let touch_update = plugin
+        .touch
+        .handle_report(events, device_report, false, now)?;
+    for name in touch_update.haptic_patterns {
+        trace!("[HID thread] haptic pattern {} not supported", name);
+    }
+    return Ok(());
+}
+
+fn config_update(plugin: &mut SonyPlugin, config_str: String) {
+    plugin.config = match serde_json::from_str(config_str.as_str()) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to parse config, keeping the previous one: {}", e);
+            return;
+        }
+    };
+    plugin
+        .touch
+        .config_update(&plugin.config.touch, ClosePolicy::OnRelease);
+}
+
+/// Reopens the device, retrying with exponential backoff.
+fn reconnect(
+    plugin: &mut SonyPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    match plugin.reconnect.attempt(NAME, hid_device_factory) {
+        Some((device, reader_device, model, name)) => {
+            info!("[HID thread] device connected: {}", name);
+            plugin.device = Some(device);
+            plugin.reports = Some(spawn_hid_reader(reader_device, 96));
+            plugin.model = model;
+            plugin.device_name = name.clone();
+            send_event(events, PluginEvent::DeviceConnected(name))?;
+        }
+        None => {}
+    }
+    return Ok(());
+}
+
+fn start_rumble(plugin: &mut SonyPlugin, rumble: &Rumble) {
+    send_rumble(plugin, rumble.left, rumble.right);
+    plugin.rumble_stop = Some(Instant::now() + Duration::from_millis(rumble.duration_ms));
+}
+
+/// Sets rumble motor speeds with an output report, only supported over USB.
+fn send_rumble(plugin: &SonyPlugin, left: u16, right: u16) {
+    let device = match &plugin.device {
+        Some(device) => device,
+        None => {
+            debug!("No device for rumble");
+            return;
+        }
+    };
+    if plugin.is_bluetooth {
+        debug!("Rumble over bluetooth is not supported");
+        return;
+    }
+    let output_report = plugin
+        .model
+        .usb_rumble_report((left >> 8) as u8, (right >> 8) as u8);
+    match device.write(&output_report) {
+        Ok(_) => {}
+        Err(e) => error!("Failed to send rumble: {}", e),
+    }
+}
+
+/// Opens the first connected DualSense or DualShock 4.
+/// Returns the device, a second handle of it for reading, its model and name.
+fn hid_device_factory() -> Option<(HidDevice, HidDevice, SonyModel, String)> {
+    let api = match hidapi::HidApi::new() {
+        Ok(api) => api,
+        Err(e) => {
+            error!("Failed to initialize hidapi {}", e);
+            return None;
+        }
+    };
+    for (pid, model, name) in SONY_DEVICES {
+        let device_info = match api
+            .device_list()
+            .filter(|device_info| device_info.vendor_id() == SONY_VID)
+            .filter(|device_info| device_info.product_id() == *pid)
+            .next()
+        {
+            Some(device_info) => device_info,
+            None => continue,
+        };
+        debug!(
+            "[HID thread] {} device path: {:?}",
+            name,
+            device_info.path()
+        );
+        // reading blocks, so the reader thread gets its own handle
+        match (device_info.open_device(&api), device_info.open_device(&api)) {
+            (Ok(device), Ok(reader_device)) => {
+                return Some((device, reader_device, *model, name.to_string()))
+            }
+            (Err(e), _) | (_, Err(e)) => error!("Failed to open {}: {}", name, e),
+        }
+    }
+    return None;
+}
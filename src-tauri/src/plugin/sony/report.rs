@@ -0,0 +1,415 @@
+use crate::plugin::steamdeck::report::{SteamDeckButton, SteamDeckButtons, SteamDeckDeviceReport};
+use crate::plugin::touch::pad_position;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SonyModel {
+    DualShock4,
+    DualSense,
+}
+
+/// Sony vendor id with product ids of supported controllers.
+pub const SONY_VID: u16 = 0x054c;
+pub static SONY_DEVICES: &[(u16, SonyModel, &str)] = &[
+    (0x05c4, SonyModel::DualShock4, "DualShock 4"),
+    (0x09cc, SonyModel::DualShock4, "DualShock 4 (v2)"),
+    (
+        0x0ba0,
+        SonyModel::DualShock4,
+        "DualShock 4 (wireless adapter)",
+    ),
+    (0x0ce6, SonyModel::DualSense, "DualSense"),
+    (0x0df2, SonyModel::DualSense, "DualSense Edge"),
+];
+
+/// Offsets of the USB input report, bluetooth reports are shifted by `bluetooth_offset`.
+struct Layout {
+    bluetooth_report_id: u8,
+    bluetooth_offset: usize,
+    triggers: usize,
+    buttons: usize,
+    touch_points: usize,
+    touch_width: u16,
+    touch_height: u16,
+}
+
+impl SonyModel {
+    fn layout(self) -> Layout {
+        return match self {
+            SonyModel::DualShock4 => Layout {
+                bluetooth_report_id: 0x11,
+                bluetooth_offset: 2,
+                triggers: 8,
+                buttons: 5,
+                touch_points: 35,
+                touch_width: 1920,
+                touch_height: 942,
+            },
+            SonyModel::DualSense => Layout {
+                bluetooth_report_id: 0x31,
+                bluetooth_offset: 1,
+                triggers: 5,
+                buttons: 8,
+                touch_points: 33,
+                touch_width: 1920,
+                touch_height: 1080,
+            },
+        };
+    }
+
+    /// Whether the report came over bluetooth, None if it contains no touchpad data.
+    pub fn is_bluetooth(self, buf: &[u8]) -> Option<bool> {
+        let layout = self.layout();
+        let (bluetooth, offset) = match buf.first() {
+            Some(0x01) => (false, 0),
+            Some(report_id) if *report_id == layout.bluetooth_report_id => {
+                (true, layout.bluetooth_offset)
+            }
+            _ => return None,
+        };
+        // the reduced bluetooth report of the DS4 is also 0x01, but too short
+        if buf.len() < layout.touch_points + offset + 8 {
+            return None;
+        }
+        return Some(bluetooth);
+    }
+
+    /// USB output report setting the rumble motor speeds.
+    ///
+    /// Bluetooth output reports (0x11 or 0x31) need a different layout
+    /// and a CRC32 and are not supported.
+    pub fn usb_rumble_report(self, left: u8, right: u8) -> Vec<u8> {
+        return match self {
+            // report id, rumble flag, 2 reserved, weak (right) and strong (left) motor
+            SonyModel::DualShock4 => {
+                let mut buf = vec![0u8; 32];
+                buf[..6].copy_from_slice(&[0x05, 0x01, 0x00, 0x00, right, left]);
+                buf
+            }
+            // report id, compatible vibration and haptics select flags, flags,
+            // right and left motor, the kernel's DS_OUTPUT_REPORT_USB_SIZE is 63
+            SonyModel::DualSense => {
+                let mut buf = vec![0u8; 63];
+                buf[..5].copy_from_slice(&[0x02, 0x03, 0x00, right, left]);
+                buf
+            }
+        };
+    }
+
+    /// Decodes an input report into the steamdeck report layout.
+    ///
+    /// Touch points on the left half of the touchpad become the left pad,
+    /// on the right half the right pad, each scaled to the full pad range.
+    /// There is no pressure sensor, so clicking the touchpad is full force on touched pads.
+    pub fn decode(self, buf: &[u8]) -> Option<SteamDeckDeviceReport> {
+        let layout = self.layout();
+        let offset = match self.is_bluetooth(buf) {
+            Some(true) => layout.bluetooth_offset,
+            Some(false) => 0,
+            None => return None,
+        };
+        let mut report = SteamDeckDeviceReport {
+            l_stick_x: stick_axis(buf[offset + 1]),
+            l_stick_y: stick_axis(255 - buf[offset + 2]),
+            r_stick_x: stick_axis(buf[offset + 3]),
+            r_stick_y: stick_axis(255 - buf[offset + 4]),
+            l_trigger: u16::from(buf[offset + layout.triggers]) << 7,
+            r_trigger: u16::from(buf[offset + layout.triggers + 1]) << 7,
+            buttons: decode_buttons(&buf[offset + layout.buttons..offset + layout.buttons + 3]),
+            ..Default::default()
+        };
+        let half_width = layout.touch_width / 2;
+        for point in 0..2 {
+            let (x, y) = match read_touch_point(buf, offset + layout.touch_points + point * 4) {
+                Some(point) => point,
+                None => continue,
+            };
+            if x < half_width {
+                (report.l_pad_x, report.l_pad_y) =
+                    touchpad_position(x, y, half_width, layout.touch_height);
+                report.buttons.set(SteamDeckButton::LPadTouch, true);
+            } else {
+                (report.r_pad_x, report.r_pad_y) =
+                    touchpad_position(x - half_width, y, half_width, layout.touch_height);
+                report.buttons.set(SteamDeckButton::RPadTouch, true);
+            }
+        }
+        if report.is_pressed(SteamDeckButton::RPadClick) {
+            let is_l_touched = report.is_pressed(SteamDeckButton::LPadTouch);
+            let is_r_touched = report.is_pressed(SteamDeckButton::RPadTouch);
+            report.l_pad_force = if is_l_touched { i16::MAX as u16 } else { 0 };
+            report.r_pad_force = if is_r_touched { i16::MAX as u16 } else { 0 };
+            report.buttons.set(SteamDeckButton::RPadClick, is_r_touched);
+            report.buttons.set(SteamDeckButton::LPadClick, is_l_touched);
+        }
+        return Some(report);
+    }
+}
+
+/// Touch point as x and y, None if not touching.
+fn read_touch_point(buf: &[u8], offset: usize) -> Option<(u16, u16)> {
+    if buf[offset] & 0x80 > 0 {
+        return None;
+    }
+    let x = u16::from(buf[offset + 1]) | u16::from(buf[offset + 2] & 0x0f) << 8;
+    let y = u16::from(buf[offset + 2] >> 4) | u16::from(buf[offset + 3]) << 4;
+    return Some((x, y));
+}
+
+/// Scales touchpad position to pad range.
+fn touchpad_position(x: u16, y: u16, width: u16, height: u16) -> (i16, i16) {
+    return pad_position(
+        f32::from(x) / f32::from(width),
+        f32::from(y) / f32::from(height),
+    );
+}
+
+/// Scales stick axis centered at 128 to pad range.
+fn stick_axis(value: u8) -> i16 {
+    return (i16::from(value) - 128) * 256;
+}
+
+/// Maps the three button bytes, shared by DS4 and DualSense, to steamdeck buttons.
+/// The touchpad click is reported as right pad click until assigned to the touched pads.
+fn decode_buttons(buf: &[u8]) -> SteamDeckButtons {
+    let mut buttons = SteamDeckButtons::default();
+    // hat switch, 0 = up, clockwise in 45 degree steps, 8 = released
+    let hat = buf[0] & 0x0f;
+    buttons.set(SteamDeckButton::DpadUp, hat == 7 || hat == 0 || hat == 1);
+    buttons.set(SteamDeckButton::DpadRight, (1..=3).contains(&hat));
+    buttons.set(SteamDeckButton::DpadDown, (3..=5).contains(&hat));
+    buttons.set(SteamDeckButton::DpadLeft, (5..=7).contains(&hat));
+    let mapping = [
+        (0, 4, SteamDeckButton::X),
+        (0, 5, SteamDeckButton::A),
+        (0, 6, SteamDeckButton::B),
+        (0, 7, SteamDeckButton::Y),
+        (1, 0, SteamDeckButton::L1),
+        (1, 1, SteamDeckButton::R1),
+        (1, 2, SteamDeckButton::L2),
+        (1, 3, SteamDeckButton::R2),
+        (1, 4, SteamDeckButton::View),
+        (1, 5, SteamDeckButton::Menu),
+        (1, 6, SteamDeckButton::LStickClick),
+        (1, 7, SteamDeckButton::RStickClick),
+        (2, 0, SteamDeckButton::Steam),
+        (2, 1, SteamDeckButton::RPadClick),
+    ];
+    for (byte, bit, button) in mapping {
+        buttons.set(button, buf[byte] & (1 << bit) > 0);
+    }
+    return buttons;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODELS: [SonyModel; 2] = [SonyModel::DualShock4, SonyModel::DualSense];
+    const FULL_FORCE: u16 = i16::MAX as u16;
+
+    /// Touch point bytes, inactive if `position` is None.
+    fn touch_point(id: u8, position: Option<(u16, u16)>) -> [u8; 4] {
+        return match position {
+            Some((x, y)) => [
+                id,
+                x as u8,
+                (x >> 8) as u8 & 0x0f | (y as u8 & 0x0f) << 4,
+                (y >> 4) as u8,
+            ],
+            None => [id | 0x80, 0, 0, 0],
+        };
+    }
+
+    /// Input report with centered sticks, released hat and the given buttons and touch points.
+    fn input_report(
+        model: SonyModel,
+        bluetooth: bool,
+        buttons: [u8; 3],
+        points: [Option<(u16, u16)>; 2],
+    ) -> Vec<u8> {
+        let layout = model.layout();
+        let (report_id, offset) = match bluetooth {
+            true => (layout.bluetooth_report_id, layout.bluetooth_offset),
+            false => (0x01, 0),
+        };
+        let mut buf = vec![0u8; 64 + offset];
+        buf[0] = report_id;
+        buf[offset + 1..offset + 5].copy_from_slice(&[128; 4]);
+        let buttons_start = offset + layout.buttons;
+        buf[buttons_start..buttons_start + 3].copy_from_slice(&buttons);
+        buf[buttons_start] |= 8;
+        for (i, position) in points.into_iter().enumerate() {
+            let start = offset + layout.touch_points + i * 4;
+            buf[start..start + 4].copy_from_slice(&touch_point(i as u8, position));
+        }
+        return buf;
+    }
+
+    #[test]
+    fn reads_touch_points() {
+        assert_eq!(
+            read_touch_point(&[0x05, 0xbc, 0x3a, 0x12], 0),
+            Some((0xabc, 0x123))
+        );
+        assert_eq!(read_touch_point(&[0x85, 0xbc, 0x3a, 0x12], 0), None);
+        for position in [(0, 0), (1919, 1079), (960, 471)] {
+            assert_eq!(
+                read_touch_point(&touch_point(1, Some(position)), 0),
+                Some(position)
+            );
+        }
+        assert_eq!(read_touch_point(&touch_point(1, None), 0), None);
+    }
+
+    #[test]
+    fn decodes_buttons() {
+        let buttons = decode_buttons(&[0x08, 0x00, 0x00]);
+        assert_eq!(buttons, SteamDeckButtons::default());
+        // hat down right, cross, L1, R2, touchpad click
+        let buttons = decode_buttons(&[0x23, 0x09, 0x02]);
+        for (button, is_pressed) in [
+            (SteamDeckButton::DpadDown, true),
+            (SteamDeckButton::DpadRight, true),
+            (SteamDeckButton::DpadUp, false),
+            (SteamDeckButton::DpadLeft, false),
+            (SteamDeckButton::A, true),
+            (SteamDeckButton::B, false),
+            (SteamDeckButton::L1, true),
+            (SteamDeckButton::R2, true),
+            (SteamDeckButton::Steam, false),
+            (SteamDeckButton::RPadClick, true),
+        ] {
+            assert_eq!(buttons.is_pressed(button), is_pressed, "{:?}", button);
+        }
+    }
+
+    #[test]
+    fn rejects_reports_without_touchpad_data() {
+        for model in MODELS {
+            let report = input_report(model, false, [0; 3], [None, None]);
+            assert_eq!(model.is_bluetooth(&report), Some(false));
+            assert!(model.decode(&report[..20]).is_none());
+            let mut report = report;
+            report[0] = 0x05;
+            assert!(model.decode(&report).is_none());
+        }
+    }
+
+    struct TouchCase {
+        name: &'static str,
+        points: [Option<(u16, u16)>; 2],
+        click: bool,
+        /// left and right pad position, None if not touched
+        pads: [Option<(i16, i16)>; 2],
+        forces: [u16; 2],
+    }
+
+    #[test]
+    fn decodes_touchpad() {
+        // (0, 0) of a half is the top left corner of that pad
+        let corner = Some((-32768, 32767));
+        let cases = [
+            TouchCase {
+                name: "untouched",
+                points: [None, None],
+                click: false,
+                pads: [None, None],
+                forces: [0, 0],
+            },
+            TouchCase {
+                name: "left half",
+                points: [Some((0, 0)), None],
+                click: false,
+                pads: [corner, None],
+                forces: [0, 0],
+            },
+            TouchCase {
+                name: "right half",
+                points: [None, Some((960, 0))],
+                click: false,
+                pads: [None, corner],
+                forces: [0, 0],
+            },
+            TouchCase {
+                name: "right half as first point",
+                points: [Some((960, 0)), None],
+                click: false,
+                pads: [None, corner],
+                forces: [0, 0],
+            },
+            TouchCase {
+                name: "click untouched",
+                points: [None, None],
+                click: true,
+                pads: [None, None],
+                forces: [0, 0],
+            },
+            TouchCase {
+                name: "click left",
+                points: [Some((0, 0)), None],
+                click: true,
+                pads: [corner, None],
+                forces: [FULL_FORCE, 0],
+            },
+            TouchCase {
+                name: "click both",
+                points: [Some((0, 0)), Some((960, 0))],
+                click: true,
+                pads: [corner, corner],
+                forces: [FULL_FORCE, FULL_FORCE],
+            },
+        ];
+        for model in MODELS {
+            for bluetooth in [false, true] {
+                for case in cases.iter() {
+                    let context = format!("{:?} bluetooth {} {}", model, bluetooth, case.name);
+                    let buttons = [0, 0, if case.click { 0x02 } else { 0 }];
+                    let buf = input_report(model, bluetooth, buttons, case.points);
+                    assert_eq!(model.is_bluetooth(&buf), Some(bluetooth), "{}", context);
+                    let report = model.decode(&buf).expect(&context);
+                    assert_eq!(
+                        (report.l_stick_x, report.l_stick_y),
+                        (0, -256),
+                        "{}",
+                        context
+                    );
+                    let pads = [
+                        (report.l_pad_x, report.l_pad_y),
+                        (report.r_pad_x, report.r_pad_y),
+                    ];
+                    let expected_pads = case.pads.map(|pad| pad.unwrap_or((0, 0)));
+                    assert_eq!(pads, expected_pads, "{}", context);
+                    let touched = [
+                        report.is_pressed(SteamDeckButton::LPadTouch),
+                        report.is_pressed(SteamDeckButton::RPadTouch),
+                    ];
+                    assert_eq!(touched, case.pads.map(|pad| pad.is_some()), "{}", context);
+                    let forces = [report.l_pad_force, report.r_pad_force];
+                    assert_eq!(forces, case.forces, "{}", context);
+                    let clicked = [
+                        report.is_pressed(SteamDeckButton::LPadClick),
+                        report.is_pressed(SteamDeckButton::RPadClick),
+                    ];
+                    assert_eq!(clicked, case.forces.map(|force| force > 0), "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scales_touch_points_per_half() {
+        for model in MODELS {
+            let layout = model.layout();
+            let (width, height) = (layout.touch_width, layout.touch_height);
+            let points = [Some((width / 2 - 1, height)), Some((width - 1, height))];
+            let buf = input_report(model, false, [0; 3], points);
+            let report = model.decode(&buf).unwrap();
+            // the last column of a half is one column short of the end of the pad range
+            let pads = [
+                (report.l_pad_x, report.l_pad_y),
+                (report.r_pad_x, report.r_pad_y),
+            ];
+            assert_eq!(pads, [(32698, -32768), (32698, -32768)], "{:?}", model);
+        }
+    }
+}
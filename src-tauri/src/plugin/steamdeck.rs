@@ -2,26 +2,77 @@ use hidapi::{DeviceInfo, HidDevice};
 use log::{debug, error, info, trace, warn};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::VecDeque,
-    fs,
-    path::Path,
-    sync::{mpsc, Mutex},
-    thread::sleep,
+    collections::HashMap,
+    sync::mpsc,
     time::{Duration, Instant, SystemTime},
 };
-use tauri::Emitter;
+use tokio::sync::mpsc::UnboundedReceiver;
 
-use crate::{toggle_window, AppState};
+use super::gesture::ClosePolicy;
+use super::haptic::{default_haptic_patterns, default_haptic_presets, HapticSequencer, HapticStep};
+use super::hid::{recv_hid_read, spawn_hid_reader, HidRead};
+use super::process::{default_steam_matchers, ProcessMatcher};
+use super::suspend::{wait_for_exit, SuspendRule, SuspendRuleState};
+use super::touch::{Reconnect, TouchConfig, TouchPipeline};
+use super::{
+    block_on, send_event, sleep_until, HapticCommand, HapticPulse, Plugin, PluginCommand,
+    PluginError, PluginEvent, Rumble,
+};
+
+mod battery;
+mod binding;
+mod profile;
+mod recording;
+pub(super) mod report;
+mod settings;
+
+use battery::read_system_battery;
+use binding::{ButtonBinding, ButtonBindingState};
+use profile::{Decoder, DeviceOverride, DEVICE_PROFILES};
+use recording::{read_recording, spawn_replay, Recorder};
+use report::{
+    check_sequence, DeviceStatus, ReportHeader, SteamDeckDeviceReport, ID_CONTROLLER_STATUS,
+    ID_CONTROLLER_WIRELESS,
+};
+use settings::{disable_steam_watchdog, set_lizard_mode, InputIsolation};
 
-use super::Plugin;
-use tauri::Manager;
+pub const NAME: &str = "steamdeck";
+/// replays a recording instead of reading the device
+pub const REPLAY_NAME: &str = "replay";
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde()]
 struct Config {
+    /// steam pid used if no process matches `steam_process`
     steam_pid: Option<i32>,
-    deadzone_dist: Option<f32>,
-    deadzone_pressure: Option<u16>,
+    /// matchers tried in order to find steam, the first matching any process is used
+    steam_process: Option<Vec<ProcessMatcher>>,
+    /// also signal descendants of steam, e.g. steamwebhelper
+    steam_process_children: Option<bool>,
+    #[serde(flatten)]
+    touch: TouchConfig,
+    gyro_pointer: Option<bool>,
+    gyro_pointer_sensitivity: Option<f32>,
+    /// toggle keyboard by button or button chord
+    toggle_button: Option<ButtonBinding>,
+    /// named haptic pulses, overriding the default presets
+    haptic_presets: Option<HashMap<String, HapticPulse>>,
+    /// named haptic patterns, overriding the default patterns
+    haptic_patterns: Option<HashMap<String, Vec<HapticStep>>>,
+    /// rumble when the keyboard is opened
+    toggle_rumble: Option<Rumble>,
+    /// device to use instead of the builtin profiles
+    device: Option<DeviceOverride>,
+    /// file raw reports are recorded to
+    record_file: Option<String>,
+    /// recording read by the replay plugin
+    replay_file: Option<String>,
+    /// replay with the recorded delays instead of as fast as possible
+    replay_realtime: Option<bool>,
+    /// stop the replay plugin at the end of the recording instead of replaying it again
+    replay_stop_at_end: Option<bool>,
+    /// how steam is kept from handling the touchpads while the keyboard is visible
+    input_isolation: Option<InputIsolation>,
 }
 
 impl Config {
@@ -30,505 +81,885 @@ impl Config {
     }
 }
 
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct SteamDeckDeviceReport {
-    l_pad_x: i16,
-    l_pad_y: i16,
-    l_pad_force: u16,
-    r_pad_x: i16,
-    r_pad_y: i16,
-    r_pad_force: u16,
-    l4: bool,
-}
-
 pub struct SteamdeckPlugin {
+    /// `NAME` or `REPLAY_NAME`
+    name: &'static str,
     config: Config,
-    left_touch_history: VecDeque<TouchEntry>,
-    right_touch_history: VecDeque<TouchEntry>,
-    deadzone_dist_square: f32,
-    deadzone_pressure: u16,
-    last_toggle_window: Instant,
-    device: HidDevice,
-    pause: bool,
-    is_visible: bool,
-    last_read: Instant,
-    last_emitted_report: SteamDeckDeviceReport,
+    /// steam processes signaled by the `signal` input isolation
+    steam: SuspendRuleState,
+    touch: TouchPipeline,
+    device: Option<HidDevice>,
+    device_name: String,
+    /// reports read by the hid reader thread
+    reports: Option<UnboundedReceiver<HidRead>>,
+    decoder: Decoder,
+    /// sequence number of the last input report
+    last_sequence: Option<u32>,
+    /// reports without valid header, for diagnostics
+    invalid_reports: u64,
+    /// count of reports by unknown report type, for diagnostics
+    unknown_reports: HashMap<u8, u64>,
+    last_status: Option<DeviceStatus>,
+    next_battery_poll: Instant,
+    recorder: Option<Recorder>,
+    /// lizard mode was turned off for input isolation and has to be restored
+    lizard_mode_disabled: bool,
+    reconnect: Reconnect,
     start: Instant,
+    gyro_pointer: (f32, f32),
+    last_gyro_update: Instant,
+    toggle_button_state: ButtonBindingState,
+    haptic_presets: HashMap<String, HapticPulse>,
+    haptic_patterns: HashMap<String, Vec<HapticStep>>,
+    haptic_sequencer: HapticSequencer,
+    rumble_stop: Option<Instant>,
 }
 
 impl SteamdeckPlugin {
     pub fn new() -> Self {
-        let mut config: Config = Config::new();
-        config.steam_pid = get_steam_pid();
-        match config.steam_pid {
-            Some(steam_pid) => send_steam_signal(steam_pid, true),
-            None => (),
-        }
+        let mut plugin = Self::with_config(NAME, Config::new());
+        plugin.steam.find_pids();
+        plugin.steam.resume();
+        return plugin;
+    }
+
+    /// Replays `replay_file` through the same pipeline, steam isn't paused.
+    pub fn replay() -> Self {
+        return Self::with_config(REPLAY_NAME, Config::new());
+    }
+
+    fn with_config(name: &'static str, config: Config) -> Self {
         return Self {
+            name: name,
+            steam: SuspendRuleState::new(steam_rule(&config)),
             config: config,
-            left_touch_history: VecDeque::new(),
-            right_touch_history: VecDeque::new(),
-            deadzone_dist_square: 500.0 * 500.0,
-            deadzone_pressure: 500,
-            last_toggle_window: Instant::now(),
-            device: hid_device_factory().unwrap(),
-            pause: false,
-            is_visible: true,
-            last_read: Instant::now(),
-            last_emitted_report: SteamDeckDeviceReport {
-                l_pad_x: -100,
-                l_pad_y: -100,
-                l_pad_force: 0,
-                r_pad_x: -100,
-                r_pad_y: -100,
-                r_pad_force: 0,
-                l4: false,
-            },
+            touch: TouchPipeline::new(),
+            device: None,
+            device_name: String::new(),
+            reports: None,
+            decoder: Decoder::SteamDeck,
+            last_sequence: None,
+            invalid_reports: 0,
+            unknown_reports: HashMap::new(),
+            last_status: None,
+            next_battery_poll: Instant::now(),
+            recorder: None,
+            lizard_mode_disabled: false,
+            reconnect: Reconnect::new(),
             start: Instant::now(),
+            gyro_pointer: (0.0, 0.0),
+            last_gyro_update: Instant::now(),
+            toggle_button_state: ButtonBindingState::default(),
+            haptic_presets: default_haptic_presets(),
+            haptic_patterns: default_haptic_patterns(),
+            haptic_sequencer: HapticSequencer::new(),
+            rumble_stop: None,
         };
     }
-}
-
-struct TouchEntry {
-    x: i16,
-    y: i16,
-    force: u16,
-    time: Instant,
-}
 
-impl TouchEntry {
-    fn is_touched(&self) -> bool {
-        return self.x != 0 || self.y != 0;
+    fn is_replay(&self) -> bool {
+        return self.name == REPLAY_NAME;
     }
 }
 
 impl Plugin for SteamdeckPlugin {
-    fn thread_fn(
+    fn run(
         &mut self,
-        app_handle: tauri::AppHandle,
-        config_rx: mpsc::Receiver<String>,
-        pause_rx: mpsc::Receiver<bool>,
-        stop_rx: mpsc::Receiver<()>,
-        trigger_haptic_rx: std::sync::mpsc::Receiver<u8>,
-    ) {
-        thread_fn(
-            self,
-            app_handle,
-            config_rx,
-            pause_rx,
-            stop_rx,
-            trigger_haptic_rx,
-        );
+        commands: UnboundedReceiver<PluginCommand>,
+        events: mpsc::Sender<PluginEvent>,
+    ) -> Result<(), PluginError> {
+        return block_on(plugin_loop(self, commands, &events));
     }
 }
 
-// read hid device and send messages to js frontend
-fn thread_fn(
+/// Handles commands, hid reports and timers as soon as they arrive,
+/// returns once stopped.
+async fn plugin_loop(
     plugin: &mut SteamdeckPlugin,
-    app_handle: tauri::AppHandle,
-    config_rx: mpsc::Receiver<String>,
-    pause_rx: mpsc::Receiver<bool>,
-    stop_rx: mpsc::Receiver<()>,
-    trigger_haptic_rx: mpsc::Receiver<u8>,
-) -> ! {
+    mut commands: UnboundedReceiver<PluginCommand>,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
     loop {
-        plugin_thread_loop(
-            plugin,
-            &app_handle,
-            &config_rx,
-            &pause_rx,
-            &stop_rx,
-            &trigger_haptic_rx,
-        );
+        let deadline = next_deadline(plugin);
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(PluginCommand::Stop) | None => {
+                    stop(plugin);
+                    return Ok(());
+                }
+                Some(command) => handle_command(plugin, command),
+            },
+            hid_read = recv_hid_read(&mut plugin.reports) => match hid_read {
+                HidRead::Report { buf, time } => handle_report(plugin, events, &buf, time)?,
+                HidRead::Failed(e) => {
+                    warn!("Failed to read device, reconnecting: {}", e);
+                    disconnect(plugin, events)?;
+                }
+                HidRead::End if plugin.config.replay_stop_at_end.unwrap_or(false) => {
+                    info!("[replay] end of recording, stopping");
+                    stop(plugin);
+                    return Ok(());
+                }
+                HidRead::End => {
+                    debug!("[replay] end of recording, restarting");
+                    disconnect(plugin, events)?;
+                }
+            },
+            _ = wait_for_exit(vec![&mut plugin.steam]) => plugin.steam.handle_exits(),
+            _ = sleep_until(deadline) => {}
+        }
+        run_timers(plugin, events)?;
     }
 }
 
-fn plugin_thread_loop(
+/// Drops the device, reconnecting once `run_timers` is due.
+fn disconnect(
     plugin: &mut SteamdeckPlugin,
-    app_handle: &tauri::AppHandle,
-    config_rx: &mpsc::Receiver<String>,
-    pause_rx: &mpsc::Receiver<bool>,
-    stop_rx: &mpsc::Receiver<()>,
-    trigger_haptic_rx: &mpsc::Receiver<u8>,
-) {
-    // stop flag
-    match stop_rx.try_recv() {
-        Ok(()) => {
-            debug!("Sending SIGCONT to steam process");
-            match plugin.config.steam_pid {
-                Some(steam_pid) => {
-                    send_steam_signal(steam_pid, true);
-                }
-                None => {}
-            };
-            debug!("Exiting now");
-            app_handle.exit(0);
-        }
-        Err(_) => {}
-    };
-    // pause flag
-    match pause_rx.try_recv() {
-        Ok(message_pause) => {
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    log_report_stats(plugin);
+    plugin.device = None;
+    plugin.reports = None;
+    plugin.reconnect.reset();
+    return send_event(
+        events,
+        PluginEvent::DeviceDisconnected(plugin.device_name.clone()),
+    );
+}
+
+fn handle_command(plugin: &mut SteamdeckPlugin, command: PluginCommand) {
+    match command {
+        PluginCommand::Pause(message_pause) => {
             debug!("[HID thread] new pause flag: {}", message_pause);
-            plugin.pause = message_pause;
+            let shown = plugin.touch.pause_update(message_pause);
             pause_update(plugin);
+            if shown {
+                plugin.gyro_pointer = (0.0, 0.0);
+                match plugin.config.toggle_rumble {
+                    Some(rumble) => start_rumble(plugin, &rumble),
+                    None => {}
+                }
+            }
         }
-        Err(_) => {}
+        PluginCommand::Config(config_str) => config_update(plugin, config_str),
+        PluginCommand::Haptic(command) => haptic_command(plugin, command),
+        // handled by the plugin loop
+        PluginCommand::Stop => {}
+    }
+}
+
+/// Earliest time a timer in `run_timers` is due.
+fn next_deadline(plugin: &SteamdeckPlugin) -> Option<Instant> {
+    let reconnect = match plugin.reports {
+        Some(_) => None,
+        None => Some(plugin.reconnect.deadline()),
+    };
+    let battery_poll = if polls_system_battery(plugin) {
+        Some(plugin.next_battery_poll)
+    } else {
+        None
     };
-    // new config
-    match config_rx.try_recv() {
-        Ok(config_str) => {
-            config_update(plugin, config_str);
+    return [
+        plugin.rumble_stop,
+        plugin.haptic_sequencer.next_due(),
+        reconnect,
+        battery_poll,
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+}
+
+/// Stops rumble, plays due haptic steps, polls the battery and reconnects the device once due.
+fn run_timers(
+    plugin: &mut SteamdeckPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    let now = Instant::now();
+    match plugin.rumble_stop {
+        Some(rumble_stop) if rumble_stop <= now => {
+            plugin.rumble_stop = None;
+            send_rumble(plugin.device.as_ref(), 0, 0);
         }
-        Err(_) => {}
-    }
-    // haptic
-    match trigger_haptic_rx.try_recv() {
-        Ok(pad) => {
-            let mut haptic_report = [0u8; 12];
-            haptic_report[0] = 0;
-            haptic_report[1] = 0x8F; // ID_TRIGGER_HAPTIC_PULSE
-            haptic_report[2] = 8; // next bytes count/size
-            haptic_report[3] = pad; // 0 = right, 1 = left, 2 = both
-            haptic_report[4] = 0xff; // duration lower byte
-            haptic_report[5] = 0xff; // duration upper byte
-            haptic_report[6] = 0x00; // interval lower byte
-            haptic_report[7] = 0x00; // interval upper byte
-            haptic_report[8] = 0x01; // count lower byte
-            haptic_report[9] = 0x00; // count upper byte
-            haptic_report[10] = 0xff; // gain
-            match plugin.device.send_feature_report(&mut haptic_report) {
-                Ok(_) => {}
-                Err(_) => {
-                    error!("Failed to send hid feature report for haptic");
-                }
-            }
+        _ => {}
+    }
+    for (pad, pulse) in plugin.haptic_sequencer.due(now) {
+        send_haptic_pulse(plugin.device.as_ref(), pad, &pulse);
+    }
+    if polls_system_battery(plugin) && plugin.next_battery_poll <= now {
+        plugin.next_battery_poll = now + Duration::from_secs(30);
+        match read_system_battery() {
+            Some(status) => emit_status(plugin, events, status)?,
+            None => {}
         }
-        Err(_) => {}
     }
-    // input
-    let mut buf = [0u8; 64];
-    let res = match plugin.device.read(&mut buf[..]) {
-        Ok(res) => res,
+    if plugin.reports.is_none() && plugin.reconnect.is_due(now) {
+        reconnect(plugin, events)?;
+    }
+    return Ok(());
+}
+
+/// The steamdeck controller has no battery status, the system battery is used instead.
+fn polls_system_battery(plugin: &SteamdeckPlugin) -> bool {
+    return !plugin.is_replay() && plugin.decoder == Decoder::SteamDeck && plugin.reports.is_some();
+}
+
+/// Sends status if changed.
+fn emit_status(
+    plugin: &mut SteamdeckPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+    mut status: DeviceStatus,
+) -> Result<(), PluginError> {
+    status.source = plugin.name;
+    if plugin.last_status.as_ref() == Some(&status) {
+        return Ok(());
+    }
+    debug!("[HID thread] device status {:?}", status);
+    plugin.last_status = Some(status.clone());
+    return send_event(events, PluginEvent::DeviceStatus(status));
+}
+
+fn handle_report(
+    plugin: &mut SteamdeckPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+    buf: &[u8],
+    now: Instant,
+) -> Result<(), PluginError> {
+    match &mut plugin.recorder {
+        Some(recorder) => recorder.write(buf, now),
+        None => {}
+    }
+    let buf: &[u8; 64] = match buf.try_into() {
+        Ok(buf) => buf,
         Err(_) => {
-            warn!("Failed to read device, reopening after short delay");
-            sleep(Duration::from_millis(1000));
-            plugin.device = match hid_device_factory() {
-                Some(device) => device,
-                None => {
-                    return;
-                }
-            };
-            return;
+            error!("USB hid response size wasn't 64 but {}", buf.len());
+            return Ok(());
         }
     };
-    if res != 64 {
-        error!("USB hid response size wasn't 64 but {}", res);
-        return;
+    let header = match ReportHeader::parse(buf) {
+        Ok(header) => header,
+        Err(e) => {
+            plugin.invalid_reports += 1;
+            trace!("[HID thread] skipping report: {}", e);
+            return Ok(());
+        }
+    };
+    if header.report_type != plugin.decoder.input_report_type() {
+        return handle_other_report(plugin, events, header, buf);
     }
-    let now = Instant::now();
-    if plugin.pause && (now - plugin.last_read).as_millis() < 50 {
-        trace!("paused and last read within 50ms, skipping");
-        return;
+    let mut device_report = match plugin.decoder.decode(buf) {
+        Ok(device_report) => device_report,
+        Err(e) => {
+            trace!("[HID thread] skipping report: {}", e);
+            return Ok(());
+        }
+    };
+    match check_sequence(plugin.last_sequence, device_report.sequence) {
+        Ok(()) => plugin.last_sequence = Some(device_report.sequence),
+        Err(e) => {
+            trace!("[HID thread] skipping report: {}", e);
+            return Ok(());
+        }
     }
-    plugin.last_read = now;
-    let device_report = SteamDeckDeviceReport {
-        l_pad_x: i16::from_le_bytes(buf[16..18].try_into().unwrap()),
-        l_pad_y: i16::from_le_bytes(buf[18..20].try_into().unwrap()),
-        l_pad_force: u16::from_le_bytes(buf[56..58].try_into().unwrap()),
-        r_pad_x: i16::from_le_bytes(buf[20..22].try_into().unwrap()),
-        r_pad_y: i16::from_le_bytes(buf[22..24].try_into().unwrap()),
-        r_pad_force: u16::from_le_bytes(buf[58..60].try_into().unwrap()),
-        l4: (u32::from_le_bytes(buf[12..16].try_into().unwrap()) & 0x200) > 0,
+    if plugin.touch.skip_paused(now) {
+        return Ok(());
+    }
+    device_report.source = plugin.name;
+    update_gyro_pointer(plugin, &mut device_report, now);
+    let button_toggle = match &plugin.config.toggle_button {
+        Some(toggle_button) => {
+            plugin
+                .toggle_button_state
+                .update(toggle_button, &device_report, now)
+        }
+        None => false,
     };
-    plugin.left_touch_history.push_back(TouchEntry {
-        x: device_report.l_pad_x,
-        y: device_report.l_pad_y,
-        force: device_report.l_pad_force,
-        time: now,
-    });
-    plugin.right_touch_history.push_back(TouchEntry {
-        x: device_report.r_pad_x,
-        y: device_report.r_pad_y,
-        force: device_report.r_pad_force,
-        time: now,
-    });
-    while !plugin.left_touch_history.is_empty() {
-        match plugin.left_touch_history.front() {
-            Some(front) => {
-                if now.duration_since(front.time).as_millis() > 2000 {
-                    plugin.left_touch_history.pop_front();
-                    continue;
-                }
+    let touch_update = plugin
+        .touch
+        .handle_report(events, device_report, button_toggle, now)?;
+    for name in touch_update.haptic_patterns {
+        play_haptic_pattern(plugin, &name, false);
+    }
+    if touch_update.toggled {
+        match plugin.haptic_presets.get("toggle") {
+            Some(pulse) => send_haptic_pulse(plugin.device.as_ref(), 2, pulse),
+            None => {}
+        }
+    }
+    return Ok(());
+}
+
+/// Handles reports other than input reports, unknown report types are counted.
+fn handle_other_report(
+    plugin: &mut SteamdeckPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+    header: ReportHeader,
+    buf: &[u8; 64],
+) -> Result<(), PluginError> {
+    match header.report_type {
+        // wireless steam controller, the receiver stays connected
+        ID_CONTROLLER_WIRELESS => match buf[4] {
+            1 => {
+                info!("[HID thread] wireless controller disconnected");
+                plugin.last_sequence = None;
+                send_event(
+                    events,
+                    PluginEvent::DeviceDisconnected(plugin.device_name.clone()),
+                )?;
+            }
+            2 => {
+                info!("[HID thread] wireless controller connected");
+                send_event(
+                    events,
+                    PluginEvent::DeviceConnected(plugin.device_name.clone()),
+                )?;
+            }
+            state => trace!("[HID thread] unknown wireless state {}", state),
+        },
+        ID_CONTROLLER_STATUS if plugin.decoder == Decoder::SteamController => {
+            match DeviceStatus::decode(buf) {
+                Ok(status) => emit_status(plugin, events, status)?,
+                Err(e) => trace!("[HID thread] skipping status report: {}", e),
             }
-            None => (),
         }
-        break;
-    }
-    while !plugin.right_touch_history.is_empty() {
-        match plugin.right_touch_history.front() {
-            Some(front) => {
-                if now.duration_since(front.time).as_millis() > 2000 {
-                    plugin.right_touch_history.pop_front();
-                    continue;
-                }
+        ID_CONTROLLER_STATUS => {
+            trace!(
+                "[HID thread] status report, payload length {}",
+                header.payload_length
+            );
+        }
+        report_type => {
+            let count = plugin.unknown_reports.entry(report_type).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                debug!(
+                    "[HID thread] unknown report type {:#04x}, payload length {}",
+                    report_type, header.payload_length
+                );
             }
-            None => (),
         }
-        break;
-    }
-    if check_keyboard_toggle(
-        &plugin.left_touch_history,
-        &plugin.right_touch_history,
-        plugin.last_toggle_window,
-        plugin.is_visible,
-    ) {
-        debug!("[HID thread] toggle window");
-        plugin.last_toggle_window = Instant::now();
-        let state = app_handle.state::<Mutex<AppState>>();
-        plugin.is_visible = toggle_window(state, app_handle.clone());
-    }
-    trace!(
-        "[HID thread] \
-        left touch history size {}, \
-        right touch history size {}",
-        plugin.left_touch_history.len(),
-        plugin.right_touch_history.len()
+    }
+    return Ok(());
+}
+
+/// Logs counts of skipped reports.
+fn log_report_stats(plugin: &SteamdeckPlugin) {
+    if plugin.invalid_reports == 0 && plugin.unknown_reports.is_empty() {
+        return;
+    }
+    debug!(
+        "[HID thread] invalid reports: {}, unknown reports by type: {:?}",
+        plugin.invalid_reports, plugin.unknown_reports
     );
-    if !plugin.pause {
-        let l_x_diff: f32 = (plugin.last_emitted_report.l_pad_x - device_report.l_pad_x).into();
-        let l_y_diff: f32 = (plugin.last_emitted_report.l_pad_y - device_report.l_pad_y).into();
-        let r_x_diff: f32 = (plugin.last_emitted_report.r_pad_x - device_report.r_pad_x).into();
-        let r_y_diff: f32 = (plugin.last_emitted_report.r_pad_y - device_report.r_pad_y).into();
-        let l_square_dist = l_x_diff * l_x_diff + l_y_diff * l_y_diff;
-        let r_square_dist = r_x_diff * r_x_diff + r_y_diff * r_y_diff;
-        let l_pressure_diff = if plugin.last_emitted_report.l_pad_force > device_report.l_pad_force
-        {
-            plugin.last_emitted_report.l_pad_force - device_report.l_pad_force
-        } else {
-            device_report.l_pad_force - plugin.last_emitted_report.l_pad_force
-        };
-        let r_pressure_diff = if plugin.last_emitted_report.r_pad_force > device_report.r_pad_force
-        {
-            plugin.last_emitted_report.r_pad_force - device_report.r_pad_force
-        } else {
-            device_report.r_pad_force - plugin.last_emitted_report.r_pad_force
-        };
-        if l_square_dist > plugin.deadzone_dist_square
-            || r_square_dist > plugin.deadzone_dist_square
-            || l_pressure_diff > plugin.deadzone_pressure
-            || r_pressure_diff > plugin.deadzone_pressure
-        {
-            plugin.last_emitted_report = device_report.clone();
-            app_handle
-                .emit("input", device_report)
-                .expect("Should be able to emit device report");
+}
+
+fn haptic_command(plugin: &mut SteamdeckPlugin, command: HapticCommand) {
+    match command {
+        HapticCommand::Pulse { pad, pulse } => {
+            send_haptic_pulse(plugin.device.as_ref(), pad, &pulse);
+        }
+        HapticCommand::Preset { pad, name } => match plugin.haptic_presets.get(&name) {
+            Some(pulse) => send_haptic_pulse(plugin.device.as_ref(), pad, pulse),
+            None => error!("Unknown haptic preset {}", name),
+        },
+        HapticCommand::Pattern { name, queue } => play_haptic_pattern(plugin, &name, queue),
+        HapticCommand::Steps { steps, queue } => {
+            plugin.haptic_sequencer.play(&steps, queue, Instant::now());
         }
+        HapticCommand::Cancel => plugin.haptic_sequencer.cancel(),
+        HapticCommand::Rumble(rumble) => start_rumble(plugin, &rumble),
     }
 }
 
+/// Resumes steam and restores lizard mode before the app exits.
+fn stop(plugin: &mut SteamdeckPlugin) {
+    log_report_stats(plugin);
+    if plugin.lizard_mode_disabled {
+        update_lizard_mode(plugin, true);
+    }
+    debug!("Resuming steam");
+    plugin.steam.resume();
+    if plugin.rumble_stop.take().is_some() {
+        send_rumble(plugin.device.as_ref(), 0, 0);
+    }
+}
+
+/// Integrates gyro angular velocity into the gyro pointer position,
+/// yaw moves the pointer horizontally and pitch vertically.
+fn update_gyro_pointer(
+    plugin: &mut SteamdeckPlugin,
+    device_report: &mut SteamDeckDeviceReport,
+    now: Instant,
+) {
+    let dt = now.duration_since(plugin.last_gyro_update).as_secs_f32();
+    plugin.last_gyro_update = now;
+    if !plugin.config.gyro_pointer.unwrap_or(false) || plugin.touch.is_paused() {
+        return;
+    }
+    // gyro resolution is 16 per degree/s, ignore small rates to avoid drift
+    let yaw = f32::from(device_report.gyro_yaw) / 16.0;
+    let pitch = f32::from(device_report.gyro_pitch) / 16.0;
+    let yaw = if yaw.abs() < 1.0 { 0.0 } else { yaw };
+    let pitch = if pitch.abs() < 1.0 { 0.0 } else { pitch };
+    // touchpad units per degree
+    let sensitivity = plugin.config.gyro_pointer_sensitivity.unwrap_or(1500.0);
+    let max = f32::from(i16::MAX);
+    plugin.gyro_pointer.0 = (plugin.gyro_pointer.0 - yaw * dt * sensitivity).clamp(-max, max);
+    plugin.gyro_pointer.1 = (plugin.gyro_pointer.1 + pitch * dt * sensitivity).clamp(-max, max);
+    device_report.gyro_pointer_x = plugin.gyro_pointer.0 as i16;
+    device_report.gyro_pointer_y = plugin.gyro_pointer.1 as i16;
+    device_report.gyro_pointer_active = true;
+}
+
+/// Isolates the touchpads from steam while the keyboard is visible.
 fn pause_update(plugin: &mut SteamdeckPlugin) {
-    if plugin.config.steam_pid.is_some() && !is_pid_alive(plugin.config.steam_pid.unwrap()) {
-        match get_steam_pid() {
-            Some(steam_pid) => plugin.config.steam_pid = Some(steam_pid),
-            None => {}
-        }
+    // replays don't touch the real device or steam
+    if plugin.is_replay() {
+        return;
     }
-    match plugin.config.steam_pid {
-        Some(steam_pid) => {
-            send_steam_signal(steam_pid, !plugin.is_visible);
+    match plugin.config.input_isolation.unwrap_or_default() {
+        InputIsolation::LizardMode => {
+            let is_visible = plugin.touch.is_visible();
+            let lizard_mode_set = update_lizard_mode(plugin, !is_visible);
+            lizard_mode_fallback(&mut plugin.steam, is_visible, lizard_mode_set);
         }
-        None => {}
+        InputIsolation::Signal => signal_update(plugin),
+        InputIsolation::None => {}
+    }
+}
+
+/// Signals steam if lizard mode couldn't be set, e.g. while the device is reconnecting.
+/// Once it could, steam is resumed if a previous fallback suspended it.
+fn lizard_mode_fallback(steam: &mut SuspendRuleState, is_visible: bool, lizard_mode_set: bool) {
+    if !lizard_mode_set {
+        warn!("Failed to set lizard mode, falling back to signal");
+        steam.update(is_visible);
+        return;
     }
+    if steam.is_suspended() {
+        debug!("Lizard mode set, resuming steam suspended by the fallback");
+        steam.resume();
+    }
+}
+
+/// Sets lizard mode of the connected device, returns false if it couldn't be set.
+fn update_lizard_mode(plugin: &mut SteamdeckPlugin, enable: bool) -> bool {
+    let device = match &plugin.device {
+        Some(device) => device,
+        None => return false,
+    };
+    return match set_lizard_mode(device, plugin.decoder, enable) {
+        Ok(_) => {
+            plugin.lizard_mode_disabled = !enable;
+            true
+        }
+        Err(e) => {
+            error!("failed to write hid settings {}", e);
+            false
+        }
+    };
+}
+
+/// Pauses steam while the keyboard is visible, resumes it otherwise.
+fn signal_update(plugin: &mut SteamdeckPlugin) {
+    plugin.steam.update(plugin.touch.is_visible());
 }
 
 fn config_update(plugin: &mut SteamdeckPlugin, config_str: String) {
-    plugin.config =
-        serde_json::from_str(config_str.as_str()).expect("Config string could not be parsed");
-    match plugin.config.deadzone_dist {
-        Some(deadzone_dist) => {
-            trace!("got deadzone_dist {}", deadzone_dist);
-            plugin.deadzone_dist_square = deadzone_dist * deadzone_dist;
+    let device_override = plugin.config.device.clone();
+    let record_file = plugin.config.record_file.clone();
+    let input_isolation = plugin.config.input_isolation;
+    let old_steam_rule = steam_rule(&plugin.config);
+    let replay = (
+        plugin.config.replay_file.clone(),
+        plugin.config.replay_realtime,
+    );
+    plugin.config = match serde_json::from_str(config_str.as_str()) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to parse config, keeping the previous one: {}", e);
+            return;
         }
+    };
+    let replay_changed = replay
+        != (
+            plugin.config.replay_file.clone(),
+            plugin.config.replay_realtime,
+        );
+    if plugin.config.device != device_override || (plugin.is_replay() && replay_changed) {
+        debug!("device override or recording changed, reconnecting");
+        plugin.device = None;
+        plugin.reports = None;
+        plugin.reconnect.reset();
+    }
+    // don't record a replay into its own recording
+    if !plugin.is_replay() && plugin.config.record_file != record_file {
+        plugin.recorder = plugin
+            .config
+            .record_file
+            .as_deref()
+            .and_then(Recorder::create);
+    }
+    plugin
+        .touch
+        .config_update(&plugin.config.touch, ClosePolicy::OnRelease);
+    plugin.haptic_presets = default_haptic_presets();
+    match &plugin.config.haptic_presets {
+        Some(haptic_presets) => plugin.haptic_presets.extend(haptic_presets.clone()),
         None => {}
     }
-    match plugin.config.deadzone_pressure {
-        Some(deadzone_pressure) => {
-            trace!("got deadzone_pressure {}", deadzone_pressure);
-            plugin.deadzone_pressure = deadzone_pressure;
-        }
+    plugin.haptic_patterns = default_haptic_patterns();
+    match &plugin.config.haptic_patterns {
+        Some(haptic_patterns) => plugin.haptic_patterns.extend(haptic_patterns.clone()),
         None => {}
     }
-    let config_steam_pid = plugin.config.steam_pid;
-    plugin.config.steam_pid = get_steam_pid();
-    if plugin.config.steam_pid.is_none() {
-        match config_steam_pid {
-            Some(steam_pid) => {
-                debug!("Using steam pid {} from config", steam_pid);
-                plugin.config.steam_pid = Some(steam_pid);
-                send_steam_signal(steam_pid, true);
-            }
-            None => {}
-        };
+    // undo the previous isolation, the new one applies on the next toggle
+    if plugin.config.input_isolation != input_isolation && plugin.lizard_mode_disabled {
+        update_lizard_mode(plugin, true);
+    }
+    // steam keeps handling the real device while replaying
+    if plugin.is_replay() {
+        return;
+    }
+    let steam_rule_changed = steam_rule(&plugin.config) != old_steam_rule;
+    let input_isolation_changed = plugin.config.input_isolation != input_isolation;
+    if steam_rule_changed || input_isolation_changed {
+        // resume with the old pids, steam would stay stopped otherwise
+        let was_suspended = plugin.steam.is_suspended();
+        plugin.steam.resume();
+        if steam_rule_changed {
+            plugin.steam = SuspendRuleState::new(steam_rule(&plugin.config));
+            plugin.steam.find_pids();
+        }
+        if was_suspended && !input_isolation_changed {
+            plugin.steam.update(plugin.touch.is_visible());
+        }
     }
 }
 
-fn hid_device_factory() -> Option<HidDevice> {
-    let api = hidapi::HidApi::new().unwrap();
-    for device in api.device_list() {
-        debug!(
-            "[HID thread] device: {:#?}, path:{:?}, vendor id: {}, product id: {}",
-            device,
-            device.path(),
-            device.vendor_id(),
-            device.product_id()
+fn play_haptic_pattern(plugin: &mut SteamdeckPlugin, name: &str, queue: bool) {
+    match plugin.haptic_patterns.get(name) {
+        Some(steps) => plugin.haptic_sequencer.play(steps, queue, Instant::now()),
+        None => error!("Unknown haptic pattern {}", name),
+    }
+}
+
+/// Reopens the device or restarts the replay, retrying with exponential backoff.
+fn reconnect(
+    plugin: &mut SteamdeckPlugin,
+    events: &mpsc::Sender<PluginEvent>,
+) -> Result<(), PluginError> {
+    let is_replay = plugin.is_replay();
+    let config = &plugin.config;
+    let connection = plugin.reconnect.attempt(plugin.name, || {
+        if is_replay {
+            return replay_factory(config).map(|(reports, name)| (None, reports, name));
+        }
+        return hid_device_factory(config.device.as_ref()).map(
+            |(device, reader_device, decoder, name)| {
+                (
+                    Some((device, decoder)),
+                    spawn_hid_reader(reader_device, 64),
+                    name,
+                )
+            },
         );
+    });
+    match connection {
+        Some((device, reports, name)) => {
+            info!("[HID thread] device connected: {}", name);
+            match device {
+                Some((device, decoder)) => {
+                    plugin.device = Some(device);
+                    plugin.decoder = decoder;
+                }
+                None => {}
+            }
+            plugin.reports = Some(reports);
+            plugin.device_name = name.clone();
+            plugin.last_sequence = None;
+            plugin.last_status = None;
+            plugin.next_battery_poll = Instant::now();
+            // the controller resets its settings when reconnecting
+            if plugin.lizard_mode_disabled && !update_lizard_mode(plugin, false) {
+                plugin.lizard_mode_disabled = false;
+            }
+            send_event(events, PluginEvent::DeviceConnected(name))?;
+        }
+        None => {}
     }
-    let (vid, pid) = (0x28de, 0x1205);
-    let device_info_opt = api
-        .device_list()
-        .filter(|device_info: &&DeviceInfo| device_info.vendor_id() == vid)
-        .filter(|device_info| device_info.product_id() == pid)
-        .filter(|device_info| device_info.interface_number() == 2)
-        .next();
-    if device_info_opt.is_none() {
-        return None;
-    }
-    let device_info = device_info_opt.unwrap();
-    debug!("[HID thread] device path: {:?}", device_info.path());
-    return match device_info.open_device(&api) {
-        Ok(device) => {
-            disable_steam_watchdog(&device);
-            Some(device)
+    return Ok(());
+}
+
+/// Starts replaying `replay_file`, returns the replayed reports and the recording name.
+fn replay_factory(config: &Config) -> Option<(UnboundedReceiver<HidRead>, String)> {
+    let replay_file = match &config.replay_file {
+        Some(replay_file) => replay_file,
+        None => {
+            debug!("[replay] no replay_file configured");
+            return None;
+        }
+    };
+    return match read_recording(replay_file) {
+        Ok(reports) => Some((
+            spawn_replay(reports, config.replay_realtime.unwrap_or(true)),
+            format!("replay {}", replay_file),
+        )),
+        Err(e) => {
+            error!("Failed to read recording {}", e);
+            None
         }
-        Err(_) => None,
     };
 }
 
-fn check_keyboard_toggle(
-    left_touch_history: &VecDeque<TouchEntry>,
-    right_touch_history: &VecDeque<TouchEntry>,
-    last_toggle_window: Instant,
-    is_visible: bool,
-) -> bool {
-    if left_touch_history.len() == 0 || right_touch_history.len() == 0 {
-        trace!("left or right touch history is empty");
-        return false;
-    }
-    let last_left_touch = left_touch_history.back().unwrap();
-    let last_right_touch = right_touch_history.back().unwrap();
-    let is_left_touched = last_left_touch.is_touched();
-    let is_right_touched = last_right_touch.is_touched();
-    if is_visible && (!is_left_touched && !is_right_touched) {
-        debug!("Visible and both touchpads released, closing keyboard");
-        return true;
-    }
-    let left_touch_time = get_last_touch_start_time(left_touch_history);
-    let right_touch_time = get_last_touch_start_time(right_touch_history);
-    if left_touch_time.is_none() || right_touch_time.is_none() {
-        trace!("left or right isn't touched");
-        return false;
-    }
-    let left_touch_time = left_touch_time.unwrap();
-    let right_touch_time = right_touch_time.unwrap();
-    if left_touch_time < last_toggle_window || right_touch_time < last_toggle_window {
-        trace!("touch time before last toggle");
-        return false;
-    }
-    let time_diff = if left_touch_time > right_touch_time {
-        left_touch_time - right_touch_time
-    } else {
-        right_touch_time - left_touch_time
+/// Sends ID_TRIGGER_HAPTIC_PULSE, pad: 0 = right, 1 = left, 2 = both
+fn send_haptic_pulse(device: Option<&HidDevice>, pad: u8, pulse: &HapticPulse) {
+    let device = match device {
+        Some(device) => device,
+        None => {
+            debug!("No device for haptic");
+            return;
+        }
     };
-    let time_diff = time_diff.as_millis();
-    trace!("time difference {}", time_diff);
-    if time_diff < 100 {
-        trace!("time difference below threshold");
-        return true;
+    let mut haptic_report = [0u8; 12];
+    haptic_report[0] = 0;
+    haptic_report[1] = 0x8F; // ID_TRIGGER_HAPTIC_PULSE
+    haptic_report[2] = 8; // next bytes count/size
+    haptic_report[3] = pad;
+    haptic_report[4..6].copy_from_slice(&pulse.duration.to_le_bytes());
+    haptic_report[6..8].copy_from_slice(&pulse.interval.to_le_bytes());
+    haptic_report[8..10].copy_from_slice(&pulse.count.to_le_bytes());
+    haptic_report[10] = pulse.gain;
+    match device.send_feature_report(&haptic_report) {
+        Ok(_) => {}
+        Err(_) => {
+            error!("Failed to send hid feature report for haptic");
+        }
     }
-    return false;
 }
 
-fn get_last_touch_start_time(touch_history: &VecDeque<TouchEntry>) -> Option<Instant> {
-    if touch_history.is_empty() {
-        return None;
-    }
-    let mut curr = touch_history.back().unwrap();
-    for prev in touch_history.iter().rev() {
-        if curr.is_touched() && !prev.is_touched() {
-            return Some(curr.time);
+fn start_rumble(plugin: &mut SteamdeckPlugin, rumble: &Rumble) {
+    send_rumble(plugin.device.as_ref(), rumble.left, rumble.right);
+    plugin.rumble_stop = Some(Instant::now() + Duration::from_millis(rumble.duration_ms));
+}
+
+/// Sends ID_TRIGGER_RUMBLE_CMD with left/right motor speed, 0 stops the motor.
+fn send_rumble(device: Option<&HidDevice>, left: u16, right: u16) {
+    let device = match device {
+        Some(device) => device,
+        None => {
+            debug!("No device for rumble");
+            return;
+        }
+    };
+    // see steam_haptic_rumble in hid-steam.c
+    let mut rumble_report = [0u8; 12];
+    rumble_report[0] = 0;
+    rumble_report[1] = 0xEB; // ID_TRIGGER_RUMBLE_CMD
+    rumble_report[2] = 9; // next bytes count/size
+    rumble_report[3] = 0;
+    rumble_report[4..6].copy_from_slice(&0u16.to_le_bytes()); // intensity
+    rumble_report[6..8].copy_from_slice(&left.to_le_bytes());
+    rumble_report[8..10].copy_from_slice(&right.to_le_bytes());
+    rumble_report[10] = 2; // left gain
+    rumble_report[11] = 0; // right gain
+    match device.send_feature_report(&rumble_report) {
+        Ok(_) => {}
+        Err(_) => {
+            error!("Failed to send hid feature report for rumble");
         }
-        curr = prev;
     }
-    return None;
 }
 
-/// Find steam pid in /proc
-fn get_steam_pid() -> Option<i32> {
-    let paths = fs::read_dir("/proc").unwrap();
-    for path in paths {
-        let path = match path {
-            Ok(path) => path,
-            Err(_) => continue,
-        };
-        let pid = match path.file_name().to_str().unwrap().parse::<i32>() {
-            Ok(pid) => pid,
-            Err(_) => continue,
-        };
-        let mut proc_exe_path = path.path().clone();
-        proc_exe_path.push("exe");
-        let exe_path_result = fs::read_link(proc_exe_path);
-        let exe_path = match exe_path_result {
-            Ok(exe) => exe,
-            Err(_) => continue,
+/// Opens the override device if configured, otherwise the first device matching a profile.
+/// Returns the device, a second handle of it for reading, its decoder and name.
+fn hid_device_factory(
+    device_override: Option<&DeviceOverride>,
+) -> Option<(HidDevice, HidDevice, Decoder, String)> {
+    let api = match hidapi::HidApi::new() {
+        Ok(api) => api,
+        Err(e) => {
+            error!("Failed to initialize hidapi {}", e);
+            return None;
+        }
+    };
+    for device in api.device_list() {
+        trace!(
+            "[HID thread] device: {:#?}, path:{:?}, vendor id: {}, product id: {}",
+            device,
+            device.path(),
+            device.vendor_id(),
+            device.product_id()
+        );
+    }
+    let mut candidates: Vec<(u16, u16, i32, Decoder, String)> = Vec::new();
+    match device_override {
+        Some(device) => candidates.push((
+            device.vendor_id,
+            device.product_id,
+            device.interface,
+            device.decoder,
+            format!("{:04x}:{:04x}", device.vendor_id, device.product_id),
+        )),
+        None => {}
+    }
+    for profile in DEVICE_PROFILES {
+        candidates.push((
+            profile.vendor_id,
+            profile.product_id,
+            profile.interface,
+            profile.decoder,
+            profile.name.to_string(),
+        ));
+    }
+    for (vid, pid, interface, decoder, name) in candidates {
+        let device_info_opt = api
+            .device_list()
+            .filter(|device_info: &&DeviceInfo| device_info.vendor_id() == vid)
+            .filter(|device_info| device_info.product_id() == pid)
+            .filter(|device_info| device_info.interface_number() == interface)
+            .next();
+        let device_info = match device_info_opt {
+            Some(device_info) => device_info,
+            None => continue,
         };
-        if exe_path.to_str().unwrap() == "/home/deck/.local/share/Steam/ubuntu12_32/steam" {
-            info!("steam pid {}", pid);
-            return Some(pid);
+        debug!(
+            "[HID thread] {} device path: {:?}",
+            name,
+            device_info.path()
+        );
+        // reading blocks, so the reader thread gets its own handle
+        match (device_info.open_device(&api), device_info.open_device(&api)) {
+            (Ok(device), Ok(reader_device)) => {
+                match disable_steam_watchdog(&device) {
+                    Ok(_) => {}
+                    Err(e) => error!("failed to write hid settings {}", e),
+                }
+                return Some((device, reader_device, decoder, name));
+            }
+            (Err(e), _) | (_, Err(e)) => error!("Failed to open {}: {}", name, e),
         }
     }
-    warn!("steam pid not found");
     return None;
 }
 
-fn is_pid_alive(pid: i32) -> bool {
-    let pid_path = format!("/proc/{}", pid);
-    let result = Path::new(pid_path.as_str()).exists();
-    debug!("Checking if pid is alive, {}: {}", pid_path, result);
-    return result;
+/// Suspends steam with the configured or default matchers,
+/// falls back to `steam_pid` from the config.
+fn steam_rule(config: &Config) -> SuspendRule {
+    return SuspendRule {
+        name: Some("steam".to_string()),
+        process: config
+            .steam_process
+            .clone()
+            .unwrap_or_else(default_steam_matchers),
+        pid: config.steam_pid,
+        include_children: config.steam_process_children,
+        action: None,
+        when: None,
+    };
 }
 
-/// Pauses or resumes steam process by pid to disable touchpad handling by steam.
-fn send_steam_signal(steam_pid: i32, is_visible: bool) {
-    let signal;
-    if !is_visible {
-        debug!("Sending stop signal to steam process");
-        signal = libc::SIGSTOP;
-    } else {
-        debug!("Sending continue signal to steam process");
-        signal = libc::SIGCONT;
-    }
-    unsafe {
-        libc::kill(steam_pid, signal);
-    }
-}
-
-// Disable steam watchdog, so when pausing steam process
-// the steamdeck controller doesn't reset itself to default hid settings
-fn disable_steam_watchdog(device: &HidDevice) {
-    // see https://github.com/torvalds/linux/blob/master/drivers/hid/hid-steam.c
-    debug!("disabling steam watchdog");
-    let mut buf = [0u8; 5];
-    buf[0] = 0x87; // ID_SET_SETTINGS_VALUES
-    buf[1] = 3; // number of settings bytes (without first two)
-    buf[2] = 71; // SETTING_STEAM_WATCHDOG_ENABLE
-    buf[3] = 0;
-    buf[4] = 0;
-    match device.send_feature_report(&buf) {
-        Ok(_) => {}
-        Err(e) => {
-            error!("failed to write hid settings {}", e);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::suspend::tests::{pid_rule, wait_for_state, KillOnDrop};
+    use tokio::sync::mpsc::unbounded_channel;
+
+    /// Replays a recording in tests/fixtures as fast as possible until its end,
+    /// returns the emitted events.
+    fn replay_fixture(name: &str, mut config: serde_json::Value) -> Vec<PluginEvent> {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        config["replay_file"] = path.into();
+        config["replay_realtime"] = false.into();
+        config["replay_stop_at_end"] = true.into();
+        let (commands_tx, commands_rx) = unbounded_channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        commands_tx
+            .send(PluginCommand::Config(config.to_string()))
+            .unwrap();
+        SteamdeckPlugin::replay()
+            .run(commands_rx, events_tx)
+            .unwrap();
+        return events_rx.try_iter().collect();
+    }
+
+    #[test]
+    fn lizard_mode_fallback_resumes_steam_once_lizard_mode_is_set() {
+        let child = KillOnDrop::sleep();
+        let pid = child.pid();
+        let mut steam = SuspendRuleState::new(pid_rule("steam", pid));
+        // shown without device, e.g. while reconnecting
+        lizard_mode_fallback(&mut steam, true, false);
+        assert!(steam.is_suspended());
+        assert!(wait_for_state(pid, true));
+        // hidden with the device back
+        lizard_mode_fallback(&mut steam, false, true);
+        assert!(!steam.is_suspended());
+        assert!(wait_for_state(pid, false));
+        // exits don't suspend it again
+        steam.handle_exits();
+        assert!(wait_for_state(pid, false));
+    }
+
+    #[test]
+    fn replay_emits_toggle_gesture_and_input() {
+        let events = replay_fixture(
+            "steamdeck_gestures.rec",
+            serde_json::json!({
+                "gestures": [
+                    {"gesture": {"type": "both_pads"}, "action": {"type": "toggle"}},
+                    {
+                        "gesture": {"type": "swipe", "pad": "right", "direction": "left"},
+                        "action": {"type": "send_key", "key": "backspace"}
+                    }
+                ]
+            }),
+        );
+        assert!(matches!(
+            events.first(),
+            Some(PluginEvent::DeviceConnected(_))
+        ));
+        let toggles = events
+            .iter()
+            .filter(|event| matches!(event, PluginEvent::ToggleWindow))
+            .count();
+        assert_eq!(toggles, 1);
+        let keys: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                PluginEvent::SendKey(key) => Some(key.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(keys, vec!["backspace"]);
+        let inputs: Vec<&SteamDeckDeviceReport> = events
+            .iter()
+            .filter_map(|event| match event {
+                PluginEvent::Input(report) => Some(report),
+                _ => None,
+            })
+            .collect();
+        assert!(inputs.iter().all(|report| report.source == REPLAY_NAME));
+        assert!(inputs.iter().any(|report| {
+            (report.l_pad_x, report.l_pad_y, report.r_pad_x, report.r_pad_y)
+                == (-10000, 5000, 10000, 5000)
+        }));
+        let last = inputs.last().unwrap();
+        assert_eq!((last.r_pad_x, last.r_pad_y), (0, 0));
+    }
+
+    #[test]
+    fn replay_without_bindings_only_emits_input() {
+        let events = replay_fixture(
+            "steamdeck_gestures.rec",
+            serde_json::json!({"gestures": [], "touch_toggle": false}),
+        );
+        assert!(events.iter().all(|event| matches!(
+            event,
+            PluginEvent::DeviceConnected(_) | PluginEvent::Input(_)
+        )));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, PluginEvent::Input(_))));
     }
 }
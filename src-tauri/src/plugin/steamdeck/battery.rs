@@ -0,0 +1,60 @@
+use log::trace;
+use std::{fs, path::Path};
+
+use super::report::DeviceStatus;
+
+const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+/// Reads the system battery, e.g. BAT1 on the steamdeck,
+/// as the steamdeck controller doesn't report the battery itself.
+pub fn read_system_battery() -> Option<DeviceStatus> {
+    let entries = match fs::read_dir(POWER_SUPPLY_PATH) {
+        Ok(entries) => entries,
+        Err(e) => {
+            trace!("Failed to read {}: {}", POWER_SUPPLY_PATH, e);
+            return None;
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if read_attribute(&path, "type").as_deref() != Some("Battery") {
+            continue;
+        }
+        // batteries of peripherals, e.g. a connected controller
+        if read_attribute(&path, "scope").as_deref() == Some("Device") {
+            continue;
+        }
+        let battery_level = match read_attribute(&path, "capacity") {
+            Some(capacity) => capacity
+                .parse::<u8>()
+                .ok()
+                .map(|capacity| capacity.min(100)),
+            None => None,
+        };
+        // in microvolts
+        let voltage_mv = match read_attribute(&path, "voltage_now") {
+            Some(voltage) => voltage
+                .parse::<u32>()
+                .ok()
+                .map(|voltage| (voltage / 1000) as u16),
+            None => None,
+        };
+        let charging = read_attribute(&path, "status").map(|status| status == "Charging");
+        return Some(DeviceStatus {
+            battery_level: battery_level,
+            voltage_mv: voltage_mv,
+            charging: charging,
+            ..Default::default()
+        });
+    }
+    return None;
+}
+
+fn read_attribute(path: &Path, name: &str) -> Option<String> {
+    return fs::read_to_string(path.join(name))
+        .ok()
+        .map(|value| value.trim().to_string());
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use super::report::{SteamDeckButton, SteamDeckDeviceReport};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonTrigger {
+    /// fires as soon as all buttons are pressed
+    #[default]
+    Press,
+    /// fires once all buttons are held for `hold_ms`
+    Hold,
+    /// fires on release if all buttons were held shorter than `hold_ms`
+    Tap,
+}
+
+/// Button or chord of buttons, e.g. `{"buttons": ["quick_access", "x"], "trigger": "press"}`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ButtonBinding {
+    pub buttons: Vec<SteamDeckButton>,
+    #[serde(default)]
+    pub trigger: ButtonTrigger,
+    pub hold_ms: Option<u64>,
+    pub debounce_ms: Option<u64>,
+}
+
+impl ButtonBinding {
+    fn hold_duration(&self) -> Duration {
+        return Duration::from_millis(self.hold_ms.unwrap_or(500));
+    }
+
+    fn debounce_duration(&self) -> Duration {
+        return Duration::from_millis(self.debounce_ms.unwrap_or(300));
+    }
+
+    fn is_pressed(&self, report: &SteamDeckDeviceReport) -> bool {
+        return !self.buttons.is_empty()
+            && self.buttons.iter().all(|button| report.is_pressed(*button));
+    }
+}
+
+#[derive(Default)]
+pub struct ButtonBindingState {
+    pressed_since: Option<Instant>,
+    fired: bool,
+    last_fired: Option<Instant>,
+}
+
+impl ButtonBindingState {
+    /// Updates chord state with the latest report,
+    /// returns true if the binding fired and isn't within the debounce time.
+    pub fn update(
+        &mut self,
+        binding: &ButtonBinding,
+        report: &SteamDeckDeviceReport,
+        now: Instant,
+    ) -> bool {
+        let fire = if binding.is_pressed(report) {
+            let pressed_since = *self.pressed_since.get_or_insert(now);
+            let fire = match binding.trigger {
+                ButtonTrigger::Press => !self.fired,
+                ButtonTrigger::Hold => {
+                    !self.fired && now.duration_since(pressed_since) >= binding.hold_duration()
+                }
+                ButtonTrigger::Tap => false,
+            };
+            self.fired |= fire;
+            fire
+        } else {
+            let fire = match self.pressed_since {
+                Some(pressed_since) => {
+                    binding.trigger == ButtonTrigger::Tap
+                        && now.duration_since(pressed_since) < binding.hold_duration()
+                }
+                None => false,
+            };
+            self.pressed_since = None;
+            self.fired = false;
+            fire
+        };
+        if !fire {
+            return false;
+        }
+        match self.last_fired {
+            Some(last_fired) if now.duration_since(last_fired) < binding.debounce_duration() => {
+                return false;
+            }
+            _ => {}
+        }
+        self.last_fired = Some(now);
+        return true;
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+use super::report::{
+    DecodeError, SteamDeckDeviceReport, ID_CONTROLLER_DECK_STATE, ID_CONTROLLER_STATE,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decoder {
+    SteamDeck,
+    SteamController,
+}
+
+impl Decoder {
+    /// Report type of input reports, see `decode`.
+    pub fn input_report_type(self) -> u8 {
+        return match self {
+            Decoder::SteamDeck => ID_CONTROLLER_DECK_STATE,
+            Decoder::SteamController => ID_CONTROLLER_STATE,
+        };
+    }
+
+    /// Decodes an input report, other reports are an error.
+    pub fn decode(self, buf: &[u8; 64]) -> Result<SteamDeckDeviceReport, DecodeError> {
+        return match self {
+            Decoder::SteamDeck => SteamDeckDeviceReport::decode(buf),
+            Decoder::SteamController => SteamDeckDeviceReport::decode_steam_controller(buf),
+        };
+    }
+}
+
+pub struct DeviceProfile {
+    pub name: &'static str,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// hid interface of the controller, other interfaces are keyboard/mouse emulation
+    pub interface: i32,
+    pub decoder: Decoder,
+}
+
+pub static DEVICE_PROFILES: &[DeviceProfile] = &[
+    DeviceProfile {
+        name: "Steam Deck",
+        vendor_id: 0x28de,
+        product_id: 0x1205,
+        interface: 2,
+        decoder: Decoder::SteamDeck,
+    },
+    DeviceProfile {
+        name: "Steam Controller",
+        vendor_id: 0x28de,
+        product_id: 0x1102,
+        interface: 2,
+        decoder: Decoder::SteamController,
+    },
+    DeviceProfile {
+        name: "Steam Controller (wireless)",
+        vendor_id: 0x28de,
+        product_id: 0x1142,
+        interface: 1,
+        decoder: Decoder::SteamController,
+    },
+];
+
+/// Device from config, tried before the builtin profiles,
+/// e.g. `{"vendor_id": 10462, "product_id": 4418, "interface": 2, "decoder": "steam_controller"}`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DeviceOverride {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub interface: i32,
+    pub decoder: Decoder,
+}
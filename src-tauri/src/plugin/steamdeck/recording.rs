@@ -0,0 +1,107 @@
+use log::{debug, error, info};
+use std::{
+    fs::{self, File},
+    io::{LineWriter, Write},
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use super::super::hid::HidRead;
+
+/// Writes raw reports with their timestamp, one report per line:
+/// `<microseconds since recording start> <report as hex>`
+pub struct Recorder {
+    file: LineWriter<File>,
+    start: Option<Instant>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Option<Self> {
+        return match File::create(path) {
+            Ok(file) => {
+                info!("Recording reports to {}", path);
+                Some(Self {
+                    file: LineWriter::new(file),
+                    start: None,
+                })
+            }
+            Err(e) => {
+                error!("Failed to create recording {}: {}", path, e);
+                None
+            }
+        };
+    }
+
+    pub fn write(&mut self, buf: &[u8], time: Instant) {
+        let start = *self.start.get_or_insert(time);
+        let hex: String = buf.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let line = format!("{} {}\n", time.duration_since(start).as_micros(), hex);
+        match self.file.write_all(line.as_bytes()) {
+            Ok(()) => {}
+            Err(e) => error!("Failed to write recording: {}", e),
+        }
+    }
+}
+
+/// Parses a recording, empty lines and lines starting with `#` are skipped.
+pub fn read_recording(path: &str) -> Result<Vec<(Duration, Vec<u8>)>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut reports = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (elapsed_us, hex) = match line.split_once(' ') {
+            Some(columns) => columns,
+            None => return Err(format!("{}:{}: expected time and report", path, i + 1)),
+        };
+        let elapsed_us = elapsed_us
+            .parse::<u64>()
+            .map_err(|e| format!("{}:{}: invalid time: {}", path, i + 1, e))?;
+        if hex.len() % 2 != 0 {
+            return Err(format!("{}:{}: odd report length", path, i + 1));
+        }
+        let buf = (0..hex.len())
+            .step_by(2)
+            .map(|j| u8::from_str_radix(&hex[j..j + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|e| format!("{}:{}: invalid report: {}", path, i + 1, e))?;
+        reports.push((Duration::from_micros(elapsed_us), buf));
+    }
+    return Ok(reports);
+}
+
+/// Sends the reports of a recording like the hid reader,
+/// with the recorded delays or as fast as possible.
+/// Ends with `HidRead::End`.
+pub fn spawn_replay(
+    reports: Vec<(Duration, Vec<u8>)>,
+    realtime: bool,
+) -> UnboundedReceiver<HidRead> {
+    let (reports_tx, reports_rx) = unbounded_channel::<HidRead>();
+    thread::spawn(move || {
+        let start = Instant::now();
+        for (elapsed, buf) in reports {
+            // report time as recorded, so gestures behave the same when replaying fast
+            let time = start + elapsed;
+            if realtime {
+                thread::sleep(time.saturating_duration_since(Instant::now()));
+            }
+            if reports_tx
+                .send(HidRead::Report {
+                    buf: buf,
+                    time: time,
+                })
+                .is_err()
+            {
+                debug!("[replay] stopped");
+                return;
+            }
+        }
+        debug!("[replay] end of recording");
+        let _ = reports_tx.send(HidRead::End);
+    });
+    return reports_rx;
+}
@@ -0,0 +1,681 @@
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// ID_CONTROLLER_STATE, steam controller input
+pub const ID_CONTROLLER_STATE: u8 = 0x01;
+/// ID_CONTROLLER_WIRELESS, wireless steam controller connected or disconnected
+pub const ID_CONTROLLER_WIRELESS: u8 = 0x03;
+/// ID_CONTROLLER_STATUS, battery and status
+pub const ID_CONTROLLER_STATUS: u8 = 0x04;
+/// ID_CONTROLLER_DECK_STATE, steamdeck input
+pub const ID_CONTROLLER_DECK_STATE: u8 = 0x09;
+
+/// Bytes after the 4 byte header.
+const MAX_PAYLOAD_LENGTH: u8 = 60;
+
+/// First 4 bytes of every report, followed by the payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportHeader {
+    pub report_type: u8,
+    pub payload_length: u8,
+}
+
+impl ReportHeader {
+    pub fn parse(buf: &[u8; 64]) -> Result<Self, DecodeError> {
+        if buf[0] != 0x01 || buf[1] != 0x00 {
+            return Err(DecodeError::InvalidHeader(buf[0], buf[1]));
+        }
+        if buf[3] > MAX_PAYLOAD_LENGTH {
+            return Err(DecodeError::InvalidLength(buf[3]));
+        }
+        return Ok(Self {
+            report_type: buf[2],
+            payload_length: buf[3],
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeError {
+    /// first two bytes aren't 0x01 0x00, not a steam controller report
+    InvalidHeader(u8, u8),
+    /// payload length exceeds the report
+    InvalidLength(u8),
+    /// valid report which isn't the expected input report, e.g. status or battery
+    UnexpectedReportType(u8),
+    /// sequence number didn't increase, report is repeated or out of order
+    StaleSequence { last: u32, sequence: u32 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            DecodeError::InvalidHeader(first, second) => {
+                write!(f, "invalid header {:#04x} {:#04x}", first, second)
+            }
+            DecodeError::InvalidLength(length) => write!(f, "invalid payload length {}", length),
+            DecodeError::UnexpectedReportType(report_type) => {
+                write!(f, "unexpected report type {:#04x}", report_type)
+            }
+            DecodeError::StaleSequence { last, sequence } => {
+                write!(f, "stale sequence number {} after {}", sequence, last)
+            }
+        };
+    }
+}
+
+/// Physical buttons of the steamdeck controller.
+///
+/// Bit positions are relative to the 64 bit button field at offset 8 of the input report,
+/// see https://github.com/torvalds/linux/blob/master/drivers/hid/hid-steam.c
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SteamDeckButton {
+    R2,
+    L2,
+    R1,
+    L1,
+    Y,
+    B,
+    X,
+    A,
+    DpadUp,
+    DpadRight,
+    DpadLeft,
+    DpadDown,
+    View,
+    Steam,
+    Menu,
+    L5,
+    R5,
+    LPadClick,
+    RPadClick,
+    LPadTouch,
+    RPadTouch,
+    LStickClick,
+    RStickClick,
+    L4,
+    R4,
+    LStickTouch,
+    RStickTouch,
+    QuickAccess,
+}
+
+impl SteamDeckButton {
+    pub const ALL: [SteamDeckButton; 28] = [
+        SteamDeckButton::R2,
+        SteamDeckButton::L2,
+        SteamDeckButton::R1,
+        SteamDeckButton::L1,
+        SteamDeckButton::Y,
+        SteamDeckButton::B,
+        SteamDeckButton::X,
+        SteamDeckButton::A,
+        SteamDeckButton::DpadUp,
+        SteamDeckButton::DpadRight,
+        SteamDeckButton::DpadLeft,
+        SteamDeckButton::DpadDown,
+        SteamDeckButton::View,
+        SteamDeckButton::Steam,
+        SteamDeckButton::Menu,
+        SteamDeckButton::L5,
+        SteamDeckButton::R5,
+        SteamDeckButton::LPadClick,
+        SteamDeckButton::RPadClick,
+        SteamDeckButton::LPadTouch,
+        SteamDeckButton::RPadTouch,
+        SteamDeckButton::LStickClick,
+        SteamDeckButton::RStickClick,
+        SteamDeckButton::L4,
+        SteamDeckButton::R4,
+        SteamDeckButton::LStickTouch,
+        SteamDeckButton::RStickTouch,
+        SteamDeckButton::QuickAccess,
+    ];
+
+    /// Bit in the button field, byte n bit m of the report is bit (n - 8) * 8 + m.
+    fn bit(self) -> u32 {
+        match self {
+            SteamDeckButton::R2 => 0,
+            SteamDeckButton::L2 => 1,
+            SteamDeckButton::R1 => 2,
+            SteamDeckButton::L1 => 3,
+            SteamDeckButton::Y => 4,
+            SteamDeckButton::B => 5,
+            SteamDeckButton::X => 6,
+            SteamDeckButton::A => 7,
+            SteamDeckButton::DpadUp => 8,
+            SteamDeckButton::DpadRight => 9,
+            SteamDeckButton::DpadLeft => 10,
+            SteamDeckButton::DpadDown => 11,
+            SteamDeckButton::View => 12,
+            SteamDeckButton::Steam => 13,
+            SteamDeckButton::Menu => 14,
+            SteamDeckButton::L5 => 15,
+            SteamDeckButton::R5 => 16,
+            SteamDeckButton::LPadClick => 17,
+            SteamDeckButton::RPadClick => 18,
+            SteamDeckButton::LPadTouch => 19,
+            SteamDeckButton::RPadTouch => 20,
+            SteamDeckButton::LStickClick => 22,
+            SteamDeckButton::RStickClick => 26,
+            SteamDeckButton::L4 => 41,
+            SteamDeckButton::R4 => 42,
+            SteamDeckButton::LStickTouch => 46,
+            SteamDeckButton::RStickTouch => 47,
+            SteamDeckButton::QuickAccess => 50,
+        }
+    }
+}
+
+/// Button bitmask as sent by the controller.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SteamDeckButtons(u64);
+
+impl SteamDeckButtons {
+    pub fn is_pressed(&self, button: SteamDeckButton) -> bool {
+        return self.0 & (1 << button.bit()) > 0;
+    }
+
+    pub fn set(&mut self, button: SteamDeckButton, pressed: bool) {
+        if pressed {
+            self.0 |= 1 << button.bit();
+        } else {
+            self.0 &= !(1 << button.bit());
+        }
+    }
+}
+
+/// Serialized as map of button name to pressed state, e.g. `{"a": true, "b": false, ...}`
+impl Serialize for SteamDeckButtons {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(SteamDeckButton::ALL.len()))?;
+        for button in SteamDeckButton::ALL {
+            map.serialize_entry(&button, &self.is_pressed(button))?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SteamDeckDeviceReport {
+    pub l_pad_x: i16,
+    pub l_pad_y: i16,
+    pub l_pad_force: u16,
+    pub r_pad_x: i16,
+    pub r_pad_y: i16,
+    pub r_pad_force: u16,
+    pub l_stick_x: i16,
+    pub l_stick_y: i16,
+    pub r_stick_x: i16,
+    pub r_stick_y: i16,
+    pub l_trigger: u16,
+    pub r_trigger: u16,
+    pub buttons: SteamDeckButtons,
+    pub accel_x: i16,
+    pub accel_y: i16,
+    pub accel_z: i16,
+    pub gyro_pitch: i16,
+    pub gyro_yaw: i16,
+    pub gyro_roll: i16,
+    pub orientation_w: i16,
+    pub orientation_x: i16,
+    pub orientation_y: i16,
+    pub orientation_z: i16,
+    /// Gyro pointer position in touchpad coordinates, not part of the hid report
+    pub gyro_pointer_x: i16,
+    pub gyro_pointer_y: i16,
+    pub gyro_pointer_active: bool,
+    /// Name of the plugin which emitted the report
+    pub source: &'static str,
+    /// Sequence number of the hid report
+    #[serde(skip)]
+    pub sequence: u32,
+}
+
+impl SteamDeckDeviceReport {
+    /// Decodes a 64 byte steamdeck input report (ID_CONTROLLER_DECK_STATE).
+    pub fn decode(buf: &[u8; 64]) -> Result<Self, DecodeError> {
+        check_report_type(buf, ID_CONTROLLER_DECK_STATE)?;
+        return Ok(Self::from_buf(buf));
+    }
+
+    /// Decodes a 64 byte steam controller input report (ID_CONTROLLER_STATE).
+    pub fn decode_steam_controller(buf: &[u8; 64]) -> Result<Self, DecodeError> {
+        check_report_type(buf, ID_CONTROLLER_STATE)?;
+        return Ok(Self::from_steam_controller_buf(buf));
+    }
+
+    /// See hid-steam.c for the layout.
+    fn from_buf(buf: &[u8; 64]) -> Self {
+        return Self {
+            l_pad_x: read_i16(buf, 16),
+            l_pad_y: read_i16(buf, 18),
+            l_pad_force: read_u16(buf, 56),
+            r_pad_x: read_i16(buf, 20),
+            r_pad_y: read_i16(buf, 22),
+            r_pad_force: read_u16(buf, 58),
+            l_stick_x: read_i16(buf, 48),
+            l_stick_y: read_i16(buf, 50),
+            r_stick_x: read_i16(buf, 52),
+            r_stick_y: read_i16(buf, 54),
+            l_trigger: read_u16(buf, 44),
+            r_trigger: read_u16(buf, 46),
+            buttons: SteamDeckButtons(u64::from_le_bytes(buf[8..16].try_into().unwrap())),
+            accel_x: read_i16(buf, 24),
+            accel_y: read_i16(buf, 26),
+            accel_z: read_i16(buf, 28),
+            gyro_pitch: read_i16(buf, 30),
+            gyro_yaw: read_i16(buf, 32),
+            gyro_roll: read_i16(buf, 34),
+            orientation_w: read_i16(buf, 36),
+            orientation_x: read_i16(buf, 38),
+            orientation_y: read_i16(buf, 40),
+            orientation_z: read_i16(buf, 42),
+            gyro_pointer_x: 0,
+            gyro_pointer_y: 0,
+            gyro_pointer_active: false,
+            source: "",
+            sequence: read_u32(buf, 4),
+        };
+    }
+
+    /// The first 24 button bits match the steamdeck,
+    /// left touchpad and stick share coordinates unless both are used at once.
+    fn from_steam_controller_buf(buf: &[u8; 64]) -> Self {
+        let is_l_pad_touched = buf[10] & 0x08 > 0;
+        let is_l_pad_and_stick = buf[10] & 0x80 > 0;
+        let (l_pad, l_stick) = if is_l_pad_and_stick {
+            (
+                (read_i16(buf, 58), read_i16(buf, 60)),
+                (read_i16(buf, 16), read_i16(buf, 18)),
+            )
+        } else if is_l_pad_touched {
+            ((read_i16(buf, 16), read_i16(buf, 18)), (0, 0))
+        } else {
+            ((0, 0), (read_i16(buf, 16), read_i16(buf, 18)))
+        };
+        let buttons = SteamDeckButtons(u64::from_le_bytes([
+            buf[8],
+            buf[9],
+            buf[10] & 0x7f,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ]));
+        // no pressure sensor, use pad clicks as full force
+        let l_pad_force = if buttons.is_pressed(SteamDeckButton::LPadClick) {
+            i16::MAX as u16
+        } else {
+            0
+        };
+        let r_pad_force = if buttons.is_pressed(SteamDeckButton::RPadClick) {
+            i16::MAX as u16
+        } else {
+            0
+        };
+        return Self {
+            l_pad_x: l_pad.0,
+            l_pad_y: l_pad.1,
+            l_pad_force: l_pad_force,
+            r_pad_x: read_i16(buf, 20),
+            r_pad_y: read_i16(buf, 22),
+            r_pad_force: r_pad_force,
+            l_stick_x: l_stick.0,
+            l_stick_y: l_stick.1,
+            l_trigger: u16::from(buf[11]) << 7,
+            r_trigger: u16::from(buf[12]) << 7,
+            buttons: buttons,
+            accel_x: read_i16(buf, 28),
+            accel_y: read_i16(buf, 30),
+            accel_z: read_i16(buf, 32),
+            gyro_pitch: read_i16(buf, 34),
+            gyro_yaw: read_i16(buf, 36),
+            gyro_roll: read_i16(buf, 38),
+            orientation_w: read_i16(buf, 40),
+            orientation_x: read_i16(buf, 42),
+            orientation_y: read_i16(buf, 44),
+            orientation_z: read_i16(buf, 46),
+            sequence: read_u32(buf, 4),
+            ..Default::default()
+        };
+    }
+
+    pub fn is_pressed(&self, button: SteamDeckButton) -> bool {
+        return self.buttons.is_pressed(button);
+    }
+
+    /// Whether any value moved beyond the deadzone or any button changed since `last`.
+    pub fn exceeds_deadzone(
+        &self,
+        last: &SteamDeckDeviceReport,
+        deadzone_dist_square: f32,
+        deadzone_pressure: u16,
+    ) -> bool {
+        let l_square_dist = square_dist((last.l_pad_x, last.l_pad_y), (self.l_pad_x, self.l_pad_y));
+        let r_square_dist = square_dist((last.r_pad_x, last.r_pad_y), (self.r_pad_x, self.r_pad_y));
+        let l_stick_square_dist = square_dist(
+            (last.l_stick_x, last.l_stick_y),
+            (self.l_stick_x, self.l_stick_y),
+        );
+        let r_stick_square_dist = square_dist(
+            (last.r_stick_x, last.r_stick_y),
+            (self.r_stick_x, self.r_stick_y),
+        );
+        let gyro_pointer_square_dist = square_dist(
+            (last.gyro_pointer_x, last.gyro_pointer_y),
+            (self.gyro_pointer_x, self.gyro_pointer_y),
+        );
+        let l_pressure_diff = last.l_pad_force.abs_diff(self.l_pad_force);
+        let r_pressure_diff = last.r_pad_force.abs_diff(self.r_pad_force);
+        let l_trigger_diff = last.l_trigger.abs_diff(self.l_trigger);
+        let r_trigger_diff = last.r_trigger.abs_diff(self.r_trigger);
+        return l_square_dist > deadzone_dist_square
+            || r_square_dist > deadzone_dist_square
+            || l_stick_square_dist > deadzone_dist_square
+            || r_stick_square_dist > deadzone_dist_square
+            || gyro_pointer_square_dist > deadzone_dist_square
+            || l_pressure_diff > deadzone_pressure
+            || r_pressure_diff > deadzone_pressure
+            || l_trigger_diff > deadzone_pressure
+            || r_trigger_diff > deadzone_pressure
+            || last.buttons != self.buttons
+            || last.gyro_pointer_active != self.gyro_pointer_active;
+    }
+}
+
+/// Checks the report header and the report type byte.
+fn check_report_type(buf: &[u8; 64], report_type: u8) -> Result<(), DecodeError> {
+    let header = ReportHeader::parse(buf)?;
+    if header.report_type != report_type {
+        return Err(DecodeError::UnexpectedReportType(header.report_type));
+    }
+    return Ok(());
+}
+
+/// Accepts sequence numbers increasing since `last_sequence`, including wrap around.
+pub fn check_sequence(last_sequence: Option<u32>, sequence: u32) -> Result<(), DecodeError> {
+    let last = match last_sequence {
+        Some(last) => last,
+        None => return Ok(()),
+    };
+    let diff = sequence.wrapping_sub(last);
+    if diff == 0 || diff > u32::MAX / 2 {
+        return Err(DecodeError::StaleSequence {
+            last: last,
+            sequence: sequence,
+        });
+    }
+    return Ok(());
+}
+
+/// Battery and charging state of the device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceStatus {
+    /// battery level in percent
+    pub battery_level: Option<u8>,
+    /// battery voltage in millivolts
+    pub voltage_mv: Option<u16>,
+    pub charging: Option<bool>,
+    /// Name of the plugin which emitted the status
+    pub source: &'static str,
+}
+
+impl DeviceStatus {
+    /// Decodes a wireless steam controller status report (ID_CONTROLLER_STATUS),
+    /// see steam_do_battery_event in hid-steam.c.
+    pub fn decode(buf: &[u8; 64]) -> Result<Self, DecodeError> {
+        check_report_type(buf, ID_CONTROLLER_STATUS)?;
+        return Ok(Self {
+            battery_level: Some(buf[14].min(100)),
+            voltage_mv: Some(read_u16(buf, 12)),
+            ..Default::default()
+        });
+    }
+}
+
+fn square_dist(a: (i16, i16), b: (i16, i16)) -> f32 {
+    let x_diff = f32::from(a.0) - f32::from(b.0);
+    let y_diff = f32::from(a.1) - f32::from(b.1);
+    return x_diff * x_diff + y_diff * y_diff;
+}
+
+fn read_i16(buf: &[u8; 64], offset: usize) -> i16 {
+    return i16::from_le_bytes([buf[offset], buf[offset + 1]]);
+}
+
+fn read_u16(buf: &[u8; 64], offset: usize) -> u16 {
+    return u16::from_le_bytes([buf[offset], buf[offset + 1]]);
+}
+
+fn read_u32(buf: &[u8; 64], offset: usize) -> u32 {
+    return u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::recording::read_recording;
+    use super::*;
+
+    /// Reports of a recording in tests/fixtures.
+    fn fixture(name: &str) -> Vec<[u8; 64]> {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        return read_recording(&path)
+            .unwrap()
+            .into_iter()
+            .map(|(_, buf)| buf.try_into().unwrap())
+            .collect();
+    }
+
+    #[test]
+    fn decodes_steamdeck_idle() {
+        let reports = fixture("steamdeck.rec");
+        let report = SteamDeckDeviceReport::decode(&reports[0]).unwrap();
+        assert_eq!(report.sequence, 1);
+        assert_eq!((report.l_pad_x, report.l_pad_y), (0, 0));
+        assert_eq!((report.r_pad_x, report.r_pad_y), (0, 0));
+        assert_eq!(report.buttons, SteamDeckButtons::default());
+    }
+
+    #[test]
+    fn decodes_steamdeck_pads() {
+        let reports = fixture("steamdeck.rec");
+        let left = SteamDeckDeviceReport::decode(&reports[1]).unwrap();
+        assert_eq!(
+            (left.l_pad_x, left.l_pad_y, left.l_pad_force),
+            (-12000, 8000, 1200)
+        );
+        assert_eq!((left.r_pad_x, left.r_pad_y, left.r_pad_force), (0, 0, 0));
+        assert!(left.is_pressed(SteamDeckButton::LPadTouch));
+        assert!(!left.is_pressed(SteamDeckButton::LPadClick));
+        let right = SteamDeckDeviceReport::decode(&reports[2]).unwrap();
+        assert_eq!(
+            (right.r_pad_x, right.r_pad_y, right.r_pad_force),
+            (15000, -20000, 30000)
+        );
+        assert!(right.is_pressed(SteamDeckButton::RPadTouch));
+        assert!(right.is_pressed(SteamDeckButton::RPadClick));
+        assert!(!right.is_pressed(SteamDeckButton::LPadTouch));
+    }
+
+    #[test]
+    fn decodes_steamdeck_buttons_sticks_and_triggers() {
+        let reports = fixture("steamdeck.rec");
+        let report = SteamDeckDeviceReport::decode(&reports[3]).unwrap();
+        let pressed: Vec<SteamDeckButton> = SteamDeckButton::ALL
+            .into_iter()
+            .filter(|button| report.is_pressed(*button))
+            .collect();
+        assert_eq!(
+            pressed,
+            vec![
+                SteamDeckButton::A,
+                SteamDeckButton::L4,
+                SteamDeckButton::QuickAccess
+            ]
+        );
+        assert_eq!((report.l_stick_x, report.l_stick_y), (-32768, 32767));
+        assert_eq!((report.r_stick_x, report.r_stick_y), (100, -100));
+        assert_eq!((report.l_trigger, report.r_trigger), (32767, 1000));
+    }
+
+    #[test]
+    fn decodes_steamdeck_imu() {
+        let reports = fixture("steamdeck.rec");
+        let report = SteamDeckDeviceReport::decode(&reports[4]).unwrap();
+        assert_eq!(
+            (report.accel_x, report.accel_y, report.accel_z),
+            (10, 20, -16384)
+        );
+        assert_eq!(
+            (report.gyro_pitch, report.gyro_yaw, report.gyro_roll),
+            (160, -320, 16)
+        );
+        assert_eq!(
+            (
+                report.orientation_w,
+                report.orientation_x,
+                report.orientation_y,
+                report.orientation_z
+            ),
+            (32767, 1, 2, 3)
+        );
+    }
+
+    #[test]
+    fn rejects_non_input_reports() {
+        let reports = fixture("steamdeck.rec");
+        assert_eq!(
+            SteamDeckDeviceReport::decode(&reports[5]).unwrap_err(),
+            DecodeError::UnexpectedReportType(0x04)
+        );
+        // steamdeck reports aren't steam controller reports and the other way around
+        assert_eq!(
+            SteamDeckDeviceReport::decode_steam_controller(&reports[0]).unwrap_err(),
+            DecodeError::UnexpectedReportType(ID_CONTROLLER_DECK_STATE)
+        );
+        let steam_controller_reports = fixture("steam_controller.rec");
+        assert_eq!(
+            SteamDeckDeviceReport::decode(&steam_controller_reports[0]).unwrap_err(),
+            DecodeError::UnexpectedReportType(ID_CONTROLLER_STATE)
+        );
+    }
+
+    #[test]
+    fn parses_header() {
+        let reports = fixture("steamdeck.rec");
+        assert_eq!(
+            ReportHeader::parse(&reports[0]),
+            Ok(ReportHeader {
+                report_type: ID_CONTROLLER_DECK_STATE,
+                payload_length: 60
+            })
+        );
+        assert_eq!(
+            ReportHeader::parse(&reports[5]),
+            Ok(ReportHeader {
+                report_type: ID_CONTROLLER_STATUS,
+                payload_length: 11
+            })
+        );
+        let mut buf = reports[0];
+        buf[3] = 61;
+        assert_eq!(
+            ReportHeader::parse(&buf),
+            Err(DecodeError::InvalidLength(61))
+        );
+    }
+
+    #[test]
+    fn decodes_status() {
+        let reports = fixture("steam_controller.rec");
+        let status = DeviceStatus::decode(&reports[4]).unwrap();
+        assert_eq!(status.battery_level, Some(76));
+        assert_eq!(status.voltage_mv, Some(3912));
+        assert_eq!(status.charging, None);
+        assert_eq!(
+            DeviceStatus::decode(&reports[0]).unwrap_err(),
+            DecodeError::UnexpectedReportType(ID_CONTROLLER_STATE)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        let mut buf = fixture("steamdeck.rec")[1];
+        buf[0] = 0x02;
+        assert_eq!(
+            SteamDeckDeviceReport::decode(&buf).unwrap_err(),
+            DecodeError::InvalidHeader(0x02, 0x00)
+        );
+        assert_eq!(
+            SteamDeckDeviceReport::decode(&[0u8; 64]).unwrap_err(),
+            DecodeError::InvalidHeader(0x00, 0x00)
+        );
+    }
+
+    #[test]
+    fn decodes_steam_controller_pad_and_stick() {
+        let reports = fixture("steam_controller.rec");
+        let pad = SteamDeckDeviceReport::decode_steam_controller(&reports[0]).unwrap();
+        assert_eq!(pad.sequence, 100);
+        assert_eq!((pad.l_pad_x, pad.l_pad_y), (1000, -2000));
+        assert_eq!((pad.l_stick_x, pad.l_stick_y), (0, 0));
+        assert!(pad.is_pressed(SteamDeckButton::LPadTouch));
+        let stick = SteamDeckDeviceReport::decode_steam_controller(&reports[1]).unwrap();
+        assert_eq!((stick.l_pad_x, stick.l_pad_y), (0, 0));
+        assert_eq!((stick.l_stick_x, stick.l_stick_y), (3000, 4000));
+        let both = SteamDeckDeviceReport::decode_steam_controller(&reports[2]).unwrap();
+        assert_eq!((both.l_pad_x, both.l_pad_y), (500, 600));
+        assert_eq!((both.l_stick_x, both.l_stick_y), (-700, 800));
+        // the pad and stick flag isn't a button
+        assert!(!both.is_pressed(SteamDeckButton::LStickClick));
+    }
+
+    #[test]
+    fn decodes_steam_controller_click_and_triggers() {
+        let reports = fixture("steam_controller.rec");
+        let report = SteamDeckDeviceReport::decode_steam_controller(&reports[3]).unwrap();
+        assert_eq!((report.r_pad_x, report.r_pad_y), (-5000, 5000));
+        assert_eq!(report.r_pad_force, i16::MAX as u16);
+        assert_eq!(report.l_pad_force, 0);
+        assert_eq!((report.l_trigger, report.r_trigger), (255 << 7, 128 << 7));
+    }
+
+    #[test]
+    fn checks_sequence() {
+        assert_eq!(check_sequence(None, 0), Ok(()));
+        assert_eq!(check_sequence(Some(1), 2), Ok(()));
+        assert_eq!(check_sequence(Some(1), 10), Ok(()));
+        assert_eq!(check_sequence(Some(u32::MAX), 0), Ok(()));
+        assert_eq!(
+            check_sequence(Some(2), 2),
+            Err(DecodeError::StaleSequence {
+                last: 2,
+                sequence: 2
+            })
+        );
+        assert_eq!(
+            check_sequence(Some(2), 1),
+            Err(DecodeError::StaleSequence {
+                last: 2,
+                sequence: 1
+            })
+        );
+    }
+
+    #[test]
+    fn fixture_sequences_increase() {
+        for name in ["steamdeck.rec", "steam_controller.rec"] {
+            let mut last_sequence = None;
+            for buf in fixture(name) {
+                let sequence = read_u32(&buf, 4);
+                assert_eq!(check_sequence(last_sequence, sequence), Ok(()), "{}", name);
+                last_sequence = Some(sequence);
+            }
+        }
+    }
+}
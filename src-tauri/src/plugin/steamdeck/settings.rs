@@ -0,0 +1,89 @@
+use hidapi::{HidDevice, HidResult};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use super::profile::Decoder;
+
+// see https://github.com/torvalds/linux/blob/master/drivers/hid/hid-steam.c
+const ID_CLEAR_DIGITAL_MAPPINGS: u8 = 0x81;
+const ID_SET_DEFAULT_DIGITAL_MAPPINGS: u8 = 0x85;
+const ID_SET_SETTINGS_VALUES: u8 = 0x87;
+const ID_LOAD_DEFAULT_SETTINGS: u8 = 0x8E;
+
+const SETTING_LEFT_TRACKPAD_MODE: u8 = 7;
+const SETTING_RIGHT_TRACKPAD_MODE: u8 = 8;
+const SETTING_LEFT_TRACKPAD_CLICK_PRESSURE: u8 = 52;
+const SETTING_RIGHT_TRACKPAD_CLICK_PRESSURE: u8 = 53;
+const SETTING_STEAM_WATCHDOG_ENABLE: u8 = 71;
+
+const TRACKPAD_NONE: u16 = 7;
+
+/// How the touchpads are kept from moving the mouse while the keyboard is visible.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputIsolation {
+    /// turn off lizard mode (mouse and key emulation) of the controller,
+    /// falls back to `Signal` if the settings can't be written.
+    /// Steam still reads the touchpads through hidraw.
+    LizardMode,
+    /// pause the steam process with SIGSTOP
+    #[default]
+    Signal,
+    /// leave steam and the controller alone
+    None,
+}
+
+/// Sends a report consisting only of the report id.
+fn send_report_byte(device: &HidDevice, report_id: u8) -> HidResult<()> {
+    return device.send_feature_report(&[0, report_id]);
+}
+
+/// Writes settings as (setting, value) pairs with one ID_SET_SETTINGS_VALUES report.
+fn write_settings(device: &HidDevice, settings: &[(u8, u16)]) -> HidResult<()> {
+    let mut buf = vec![0, ID_SET_SETTINGS_VALUES, (settings.len() * 3) as u8];
+    for (setting, value) in settings {
+        buf.push(*setting);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+    return device.send_feature_report(&buf);
+}
+
+/// Enables or disables the mouse and key emulation the controller does without steam,
+/// like steam_set_lizard_mode in hid-steam.c.
+pub fn set_lizard_mode(device: &HidDevice, decoder: Decoder, enable: bool) -> HidResult<()> {
+    debug!("[HID thread] setting lizard mode: {}", enable);
+    if enable {
+        send_report_byte(device, ID_SET_DEFAULT_DIGITAL_MAPPINGS)?;
+        send_report_byte(device, ID_LOAD_DEFAULT_SETTINGS)?;
+        // loading the defaults enables the watchdog again
+        return disable_steam_watchdog(device);
+    }
+    send_report_byte(device, ID_CLEAR_DIGITAL_MAPPINGS)?;
+    return match decoder {
+        Decoder::SteamDeck => write_settings(
+            device,
+            &[
+                (SETTING_LEFT_TRACKPAD_MODE, TRACKPAD_NONE),
+                (SETTING_RIGHT_TRACKPAD_MODE, TRACKPAD_NONE),
+                // disable haptic click
+                (SETTING_LEFT_TRACKPAD_CLICK_PRESSURE, 0xFFFF),
+                (SETTING_RIGHT_TRACKPAD_CLICK_PRESSURE, 0xFFFF),
+                (SETTING_STEAM_WATCHDOG_ENABLE, 0),
+            ],
+        ),
+        Decoder::SteamController => write_settings(
+            device,
+            &[
+                (SETTING_LEFT_TRACKPAD_MODE, TRACKPAD_NONE),
+                (SETTING_RIGHT_TRACKPAD_MODE, TRACKPAD_NONE),
+            ],
+        ),
+    };
+}
+
+/// Disables steam watchdog, so when pausing steam process
+/// the steamdeck controller doesn't reset itself to default hid settings.
+pub fn disable_steam_watchdog(device: &HidDevice) -> HidResult<()> {
+    debug!("disabling steam watchdog");
+    return write_settings(device, &[(SETTING_STEAM_WATCHDOG_ENABLE, 0)]);
+}
@@ -0,0 +1,338 @@
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{future, task::Poll};
+
+use super::process::{
+    find_processes, resume_process, suspend_process, Pidfd, ProcessMatcher, Signal,
+};
+
+/// Signals sent to suspend and to resume the processes of a rule.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SuspendAction {
+    pub suspend: Signal,
+    pub resume: Signal,
+}
+
+impl Default for SuspendAction {
+    fn default() -> Self {
+        return Self {
+            suspend: Signal::Sigstop,
+            resume: Signal::Sigcont,
+        };
+    }
+}
+
+/// Keyboard visibility a rule suspends its processes in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    #[default]
+    Visible,
+    Hidden,
+}
+
+/// Suspends processes while the keyboard is visible (or hidden),
+/// e.g. input remappers grabbing the touchpads.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SuspendRule {
+    /// for logging
+    pub name: Option<String>,
+    /// matchers tried in order, the first matching any process is used
+    pub process: Vec<ProcessMatcher>,
+    /// pid used if no process matches
+    pub pid: Option<i32>,
+    /// also suspend descendants of matched processes
+    pub include_children: Option<bool>,
+    /// SIGSTOP and SIGCONT if not set
+    pub action: Option<SuspendAction>,
+    pub when: Option<Visibility>,
+}
+
+/// Rule with the processes it found.
+pub struct SuspendRuleState {
+    rule: SuspendRule,
+    processes: Vec<Pidfd>,
+    /// processes are suspended for the current visibility
+    suspended: bool,
+}
+
+impl SuspendRuleState {
+    pub fn new(rule: SuspendRule) -> Self {
+        return Self {
+            rule: rule,
+            processes: Vec::new(),
+            suspended: false,
+        };
+    }
+
+    fn name(&self) -> &str {
+        return self.rule.name.as_deref().unwrap_or("suspend rule");
+    }
+
+    /// Finds the processes of the rule again.
+    pub fn find_pids(&mut self) {
+        let include_children = self.rule.include_children.unwrap_or(false);
+        let pids = match find_processes(&self.rule.process, include_children) {
+            Some((matcher, pids)) => {
+                info!("{} pids {:?}, matched by {}", self.name(), pids, matcher);
+                pids
+            }
+            None => match self.rule.pid {
+                Some(pid) => {
+                    debug!("Using {} pid {} from config", self.name(), pid);
+                    vec![pid]
+                }
+                None => {
+                    info!("{} pid not found", self.name());
+                    Vec::new()
+                }
+            },
+        };
+        // exited but not yet reaped processes still match, skip them
+        self.processes = pids
+            .into_iter()
+            .filter_map(|pid| match Pidfd::open(pid) {
+                Ok(process) => Some(process),
+                Err(e) => {
+                    warn!("Failed to open pidfd of {} pid {}: {}", self.name(), pid, e);
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Suspends or resumes the processes for the keyboard visibility,
+    /// finding them first if there are none.
+    pub fn update(&mut self, is_visible: bool) {
+        if self.processes.is_empty() {
+            self.find_pids();
+        }
+        let when = self.rule.when.unwrap_or_default();
+        self.suspended = is_visible == (when == Visibility::Visible);
+        self.apply();
+    }
+
+    fn apply(&self) {
+        let action = self.rule.action.unwrap_or_default();
+        for process in &self.processes {
+            if self.suspended {
+                suspend_process(process, action.suspend, action.resume);
+            } else {
+                resume_process(process, action.resume);
+            }
+        }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        return self.suspended;
+    }
+
+    /// Resumes the processes, e.g. before exiting.
+    pub fn resume(&mut self) {
+        self.suspended = false;
+        self.apply();
+    }
+
+    /// Forgets exited processes and finds the processes again,
+    /// suspending them if the rule currently suspends.
+    pub fn handle_exits(&mut self) {
+        if self.processes.iter().all(|process| process.is_alive()) {
+            return;
+        }
+        let action = self.rule.action.unwrap_or_default();
+        for process in self.processes.iter().filter(|process| !process.is_alive()) {
+            info!("{} pid {} exited", self.name(), process.pid());
+            resume_process(process, action.resume);
+        }
+        self.find_pids();
+        if self.suspended {
+            self.apply();
+        }
+    }
+}
+
+/// Rules of the `suspend_rules` config, owned by the app and updated on every visibility change.
+#[derive(Default)]
+pub struct SuspendRules {
+    rules: Vec<SuspendRule>,
+    states: Vec<SuspendRuleState>,
+}
+
+impl SuspendRules {
+    pub fn new() -> Self {
+        return Self {
+            rules: Vec::new(),
+            states: Vec::new(),
+        };
+    }
+
+    /// Replaces the rules if they changed, resuming the processes of the old ones.
+    /// The new rules apply on the next visibility change.
+    pub fn config_update(&mut self, rules: Vec<SuspendRule>) {
+        if rules == self.rules {
+            return;
+        }
+        self.resume();
+        self.states = rules.iter().cloned().map(SuspendRuleState::new).collect();
+        self.rules = rules;
+    }
+
+    /// Suspends or resumes the processes of all rules for the keyboard visibility.
+    pub fn update(&mut self, is_visible: bool) {
+        for state in &mut self.states {
+            state.handle_exits();
+            state.update(is_visible);
+        }
+    }
+
+    /// Finds processes again if any exited, see `SuspendRuleState::handle_exits`.
+    pub fn handle_exits(&mut self) {
+        for state in &mut self.states {
+            state.handle_exits();
+        }
+    }
+
+    /// Resumes the processes of all rules, e.g. before exiting.
+    pub fn resume(&mut self) {
+        for state in &mut self.states {
+            state.resume();
+        }
+    }
+}
+
+/// Waits until a process of any of the rules exits, never returns without processes.
+pub async fn wait_for_exit(mut rules: Vec<&mut SuspendRuleState>) {
+    future::poll_fn(|cx| {
+        for rule in rules.iter_mut() {
+            for process in rule.processes.iter_mut() {
+                if process.poll_exited(cx).is_ready() {
+                    return Poll::Ready(());
+                }
+            }
+        }
+        return Poll::Pending;
+    })
+    .await;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// State letter of /proc/<pid>/stat, e.g. 'T' if stopped.
+    fn process_state(pid: i32) -> char {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+        let (_, fields) = stat.rsplit_once(')').unwrap();
+        return fields.trim_start().chars().next().unwrap();
+    }
+
+    /// Waits until the signal was delivered.
+    pub(crate) fn wait_for_state(pid: i32, stopped: bool) -> bool {
+        for _ in 0..100 {
+            if (process_state(pid) == 'T') == stopped {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        return false;
+    }
+
+    /// Kills the child also if the test fails, a stopped child would keep the test output open.
+    pub(crate) struct KillOnDrop(pub std::process::Child);
+
+    impl KillOnDrop {
+        pub(crate) fn sleep() -> Self {
+            let child = std::process::Command::new("sleep")
+                .arg("10")
+                .spawn()
+                .unwrap();
+            return Self(child);
+        }
+
+        pub(crate) fn pid(&self) -> i32 {
+            return self.0.id() as i32;
+        }
+    }
+
+    impl Drop for KillOnDrop {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    pub(crate) fn pid_rule(name: &str, pid: i32) -> SuspendRule {
+        return SuspendRule {
+            name: Some(name.to_string()),
+            process: Vec::new(),
+            pid: Some(pid),
+            include_children: None,
+            action: None,
+            when: None,
+        };
+    }
+
+    #[test]
+    fn suspend_rules_resume_replaced_rules() {
+        let child = KillOnDrop::sleep();
+        let pid = child.pid();
+        let mut suspend_rules = SuspendRules::new();
+        suspend_rules.config_update(vec![pid_rule("sleep", pid)]);
+        suspend_rules.update(true);
+        assert!(wait_for_state(pid, true));
+        // unchanged rules keep their processes suspended
+        suspend_rules.config_update(vec![pid_rule("sleep", pid)]);
+        assert!(wait_for_state(pid, true));
+        suspend_rules.config_update(Vec::new());
+        assert!(wait_for_state(pid, false));
+        suspend_rules.update(true);
+        assert!(wait_for_state(pid, false));
+    }
+
+    #[test]
+    fn suspend_rules_follow_visibility() {
+        let child = KillOnDrop::sleep();
+        let pid = child.pid();
+        let mut suspend_rules = SuspendRules::new();
+        let mut hidden_rule = pid_rule("sleep", pid);
+        hidden_rule.when = Some(Visibility::Hidden);
+        suspend_rules.config_update(vec![hidden_rule]);
+        suspend_rules.update(false);
+        assert!(wait_for_state(pid, true));
+        suspend_rules.update(true);
+        assert!(wait_for_state(pid, false));
+        suspend_rules.update(false);
+        assert!(wait_for_state(pid, true));
+        suspend_rules.resume();
+        assert!(wait_for_state(pid, false));
+    }
+
+    #[test]
+    fn rule_config_format() {
+        let rule: SuspendRule = serde_json::from_str(
+            r#"{
+                "name": "inputplumber",
+                "process": [{"comm": "inputplumber"}],
+                "action": {"suspend": "SIGUSR1", "resume": "SIGUSR2"},
+                "when": "hidden"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.process,
+            vec![ProcessMatcher::Comm("inputplumber".to_string())]
+        );
+        assert_eq!(rule.action.unwrap().suspend.number(), libc::SIGUSR1);
+        assert_eq!(rule.action.unwrap().resume.number(), libc::SIGUSR2);
+        assert_eq!(rule.when, Some(Visibility::Hidden));
+        assert_eq!(rule.pid, None);
+    }
+
+    #[test]
+    fn default_action_stops_and_continues() {
+        let action = SuspendAction::default();
+        assert_eq!(action.suspend.number(), libc::SIGSTOP);
+        assert_eq!(action.resume.number(), libc::SIGCONT);
+    }
+}
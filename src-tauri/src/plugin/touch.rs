@@ -0,0 +1,370 @@
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use super::gesture::{
+    default_gesture_bindings, push_touch_entry, ClosePolicy, Gesture, GestureAction,
+    GestureBinding, GestureRecognizer, GestureThresholds, TouchEntry,
+};
+use super::steamdeck::report::SteamDeckDeviceReport;
+use super::{send_event, PluginError, PluginEvent};
+
+/// Config of the touch pipeline, flattened into the config of every plugin reading touchpads.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TouchConfig {
+    pub deadzone_dist: Option<f32>,
+    pub deadzone_pressure: Option<u16>,
+    /// toggle keyboard by touching both touchpads
+    pub touch_toggle: Option<bool>,
+    pub gestures: Option<Vec<GestureBinding>>,
+    pub gesture_thresholds: Option<GestureThresholds>,
+    /// max ms between touching left and right touchpad to toggle the keyboard
+    pub toggle_simultaneity_ms: Option<u64>,
+    /// ms of touch history kept for gestures
+    pub touch_history_ms: Option<u64>,
+    pub close_policy: Option<ClosePolicy>,
+    pub close_idle_timeout_ms: Option<u64>,
+    /// keep keyboard open until toggled, regardless of close policy
+    pub sticky: Option<bool>,
+}
+
+/// Result of a report handled by the touch pipeline.
+pub struct TouchUpdate {
+    /// the keyboard is toggled
+    pub toggled: bool,
+    /// haptic patterns of recognized gestures, played by plugins supporting them
+    pub haptic_patterns: Vec<String>,
+}
+
+/// Touch history, gestures, toggling and deadzone of reports from any plugin.
+pub struct TouchPipeline {
+    left_touch_history: VecDeque<TouchEntry>,
+    right_touch_history: VecDeque<TouchEntry>,
+    touch_history_duration: Duration,
+    deadzone_dist_square: f32,
+    deadzone_pressure: u16,
+    touch_toggle: bool,
+    close_policy: ClosePolicy,
+    close_idle_timeout: Duration,
+    sticky: bool,
+    gesture_recognizer: GestureRecognizer,
+    gesture_bindings: Vec<GestureBinding>,
+    last_toggle_window: Instant,
+    last_touch: Instant,
+    last_read: Instant,
+    last_emitted_report: SteamDeckDeviceReport,
+    pause: bool,
+    is_visible: bool,
+}
+
+impl TouchPipeline {
+    pub fn new() -> Self {
+        return Self {
+            left_touch_history: VecDeque::new(),
+            right_touch_history: VecDeque::new(),
+            touch_history_duration: Duration::from_millis(2000),
+            deadzone_dist_square: 500.0 * 500.0,
+            deadzone_pressure: 500,
+            touch_toggle: true,
+            close_policy: ClosePolicy::default(),
+            close_idle_timeout: Duration::from_millis(3000),
+            sticky: false,
+            gesture_recognizer: GestureRecognizer::new(),
+            gesture_bindings: default_gesture_bindings(),
+            last_toggle_window: Instant::now(),
+            last_touch: Instant::now(),
+            last_read: Instant::now(),
+            last_emitted_report: SteamDeckDeviceReport {
+                l_pad_x: -100,
+                l_pad_y: -100,
+                r_pad_x: -100,
+                r_pad_y: -100,
+                ..Default::default()
+            },
+            pause: false,
+            is_visible: true,
+        };
+    }
+
+    /// Applies the config, `default_close_policy` is used if `close_policy` isn't set.
+    pub fn config_update(&mut self, config: &TouchConfig, default_close_policy: ClosePolicy) {
+        match config.deadzone_dist {
+            Some(deadzone_dist) => {
+                trace!("got deadzone_dist {}", deadzone_dist);
+                self.deadzone_dist_square = deadzone_dist * deadzone_dist;
+            }
+            None => {}
+        }
+        match config.deadzone_pressure {
+            Some(deadzone_pressure) => {
+                trace!("got deadzone_pressure {}", deadzone_pressure);
+                self.deadzone_pressure = deadzone_pressure;
+            }
+            None => {}
+        }
+        self.touch_toggle = config.touch_toggle.unwrap_or(true);
+        self.close_policy = config.close_policy.unwrap_or(default_close_policy);
+        self.close_idle_timeout =
+            Duration::from_millis(config.close_idle_timeout_ms.unwrap_or(3000));
+        self.sticky = config.sticky.unwrap_or(false);
+        self.gesture_bindings = config
+            .gestures
+            .clone()
+            .unwrap_or_else(default_gesture_bindings);
+        self.gesture_recognizer.thresholds = config.gesture_thresholds.clone().unwrap_or_default();
+        self.gesture_recognizer.simultaneity =
+            Duration::from_millis(config.toggle_simultaneity_ms.unwrap_or(100));
+        self.touch_history_duration =
+            Duration::from_millis(config.touch_history_ms.unwrap_or(2000));
+    }
+
+    /// Applies the pause command, returns true if the keyboard was shown.
+    pub fn pause_update(&mut self, pause: bool) -> bool {
+        let shown = self.pause && !pause;
+        self.pause = pause;
+        // another plugin may have toggled the window
+        self.is_visible = !pause;
+        return shown;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.pause;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        return self.is_visible;
+    }
+
+    /// True if the report should be skipped, while paused only one report per 50ms is handled.
+    /// Only for devices sending reports continuously, a skipped release would never be seen.
+    pub fn skip_paused(&mut self, now: Instant) -> bool {
+        if self.pause && (now - self.last_read).as_millis() < 50 {
+            trace!("paused and last read within 50ms, skipping");
+            return true;
+        }
+        self.last_read = now;
+        return false;
+    }
+
+    /// Recognizes gestures and runs their actions, toggles the keyboard by gesture,
+    /// `button_toggle` or close policy and sends the report as input if it exceeds the deadzone.
+    pub fn handle_report(
+        &mut self,
+        events: &mpsc::Sender<PluginEvent>,
+        device_report: SteamDeckDeviceReport,
+        button_toggle: bool,
+        now: Instant,
+    ) -> Result<TouchUpdate, PluginError> {
+        push_touch_entry(
+            &mut self.left_touch_history,
+            TouchEntry {
+                x: device_report.l_pad_x,
+                y: device_report.l_pad_y,
+                force: device_report.l_pad_force,
+                time: now,
+            },
+            self.touch_history_duration,
+        );
+        push_touch_entry(
+            &mut self.right_touch_history,
+            TouchEntry {
+                x: device_report.r_pad_x,
+                y: device_report.r_pad_y,
+                force: device_report.r_pad_force,
+                time: now,
+            },
+            self.touch_history_duration,
+        );
+        if device_report.l_pad_x != 0
+            || device_report.l_pad_y != 0
+            || device_report.r_pad_x != 0
+            || device_report.r_pad_y != 0
+        {
+            self.last_touch = now;
+        }
+        let gestures = self.gesture_recognizer.recognize(
+            &self.left_touch_history,
+            &self.right_touch_history,
+            self.last_toggle_window,
+            self.is_visible,
+            self.close_policy == ClosePolicy::OnRelease && !self.sticky,
+        );
+        let mut touch_toggle = false;
+        let mut toggle_sticky = false;
+        let mut haptic_patterns = Vec::new();
+        for gesture in gestures {
+            if gesture == Gesture::BothPads && !self.touch_toggle {
+                continue;
+            }
+            for binding in self.gesture_bindings.iter() {
+                if binding.gesture != gesture {
+                    continue;
+                }
+                match &binding.action {
+                    GestureAction::Toggle => touch_toggle = true,
+                    GestureAction::ToggleSticky => toggle_sticky = true,
+                    GestureAction::HapticPattern { name } => {
+                        haptic_patterns.push(name.clone());
+                    }
+                    action => run_gesture_action(events, action)?,
+                }
+            }
+        }
+        if toggle_sticky {
+            self.sticky = !self.sticky;
+            debug!("sticky {}", self.sticky);
+        }
+        let idle_close = self.is_visible
+            && !self.sticky
+            && self.close_policy == ClosePolicy::IdleTimeout
+            && now.duration_since(self.last_touch.max(self.last_toggle_window))
+                >= self.close_idle_timeout;
+        if idle_close {
+            debug!("touchpads idle, closing keyboard");
+        }
+        let toggled = touch_toggle || button_toggle || idle_close;
+        if toggled {
+            debug!("toggle window");
+            self.last_toggle_window = now;
+            // updated again by the pause command once toggled
+            self.is_visible = !self.is_visible;
+            send_event(events, PluginEvent::ToggleWindow)?;
+        }
+        trace!(
+            "left touch history size {}, right touch history size {}",
+            self.left_touch_history.len(),
+            self.right_touch_history.len()
+        );
+        if !self.pause
+            && device_report.exceeds_deadzone(
+                &self.last_emitted_report,
+                self.deadzone_dist_square,
+                self.deadzone_pressure,
+            )
+        {
+            self.last_emitted_report = device_report.clone();
+            send_event(events, PluginEvent::Input(device_report))?;
+        }
+        return Ok(TouchUpdate {
+            toggled: toggled,
+            haptic_patterns: haptic_patterns,
+        });
+    }
+}
+
+fn run_gesture_action(
+    events: &mpsc::Sender<PluginEvent>,
+    action: &GestureAction,
+) -> Result<(), PluginError> {
+    debug!("gesture action {:?}", action);
+    return match action {
+        GestureAction::Toggle
+        | GestureAction::ToggleSticky
+        | GestureAction::HapticPattern { .. } => Ok(()),
+        GestureAction::SwitchLayer { layer } => {
+            send_event(events, PluginEvent::SwitchLayer(layer.clone()))
+        }
+        GestureAction::SendKey { key } => send_event(events, PluginEvent::SendKey(key.clone())),
+        GestureAction::MoveWindow { x, y } => {
+            send_event(events, PluginEvent::MoveWindow { x: *x, y: *y })
+        }
+    };
+}
+
+/// Exponential backoff for reopening a device.
+pub struct Reconnect {
+    delay: Duration,
+    next: Instant,
+}
+
+impl Reconnect {
+    const MIN_DELAY: Duration = Duration::from_millis(250);
+    const MAX_DELAY: Duration = Duration::from_secs(5);
+
+    pub fn new() -> Self {
+        return Self {
+            delay: Self::MIN_DELAY,
+            next: Instant::now(),
+        };
+    }
+
+    /// Reconnects right away, e.g. once the device was lost.
+    pub fn reset(&mut self) {
+        self.delay = Self::MIN_DELAY;
+        self.next = Instant::now();
+    }
+
+    /// Time of the next attempt.
+    pub fn deadline(&self) -> Instant {
+        return self.next;
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        return self.next <= now;
+    }
+
+    /// Connects with `connect`, doubling the delay until the next attempt if that failed.
+    pub fn attempt<T>(&mut self, name: &str, connect: impl FnOnce() -> Option<T>) -> Option<T> {
+        let connection = connect();
+        match connection {
+            Some(_) => self.delay = Self::MIN_DELAY,
+            None => {
+                self.delay = (self.delay * 2).min(Self::MAX_DELAY);
+                self.next = Instant::now() + self.delay;
+                debug!(
+                    "[{}] device not found, retrying in {}ms",
+                    name,
+                    self.delay.as_millis()
+                );
+            }
+        }
+        return connection;
+    }
+}
+
+/// Scales a relative touch position in [0, 1] to the pad range, y pointing up like the steamdeck.
+pub fn pad_position(rel_x: f32, rel_y: f32) -> (i16, i16) {
+    let pad_x = (rel_x.clamp(0.0, 1.0) * 65535.0 - 32768.0) as i16;
+    let pad_y = (32767.0 - rel_y.clamp(0.0, 1.0) * 65535.0) as i16;
+    // (0, 0) means not touched
+    if pad_x == 0 && pad_y == 0 {
+        return (1, 0);
+    }
+    return (pad_x, pad_y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_backs_off_until_connected() {
+        let mut reconnect = Reconnect::new();
+        let mut delays = Vec::new();
+        for _ in 0..6 {
+            let before = Instant::now();
+            assert_eq!(reconnect.attempt::<()>("test", || None), None);
+            delays.push((reconnect.deadline() - before).as_millis() / 100);
+        }
+        assert_eq!(delays, vec![5, 10, 20, 40, 50, 50]);
+        assert!(!reconnect.is_due(Instant::now()));
+        assert_eq!(reconnect.attempt("test", || Some(1)), Some(1));
+        reconnect.reset();
+        assert!(reconnect.is_due(Instant::now()));
+        reconnect.attempt::<()>("test", || None);
+        assert_eq!((reconnect.deadline() - Instant::now()).as_millis() / 100, 4);
+    }
+
+    #[test]
+    fn pad_position_is_never_untouched() {
+        assert_eq!(pad_position(0.0, 0.0), (-32768, 32767));
+        assert_eq!(pad_position(1.0, 1.0), (32767, -32768));
+        assert_eq!(pad_position(2.0, -1.0), (32767, 32767));
+        // the center would be (0, 0), which means not touched
+        assert_eq!(pad_position(0.5, 0.5), (1, 0));
+    }
+}
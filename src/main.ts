@@ -14,9 +14,25 @@ async function sendKey(
   });
 }
 
-async function triggerHapticPulse(pad: number) {
-  return invoke('trigger_haptic_pulse', {
-    pad: pad
+/**
+ * @param pad 0 = right, 1 = left, 2 = both
+ * @param name preset name, e.g. 'hover', 'key_press', 'layer_switch', 'error' or 'toggle'
+ */
+async function triggerHapticPreset(pad: number, name: string) {
+  return invoke('trigger_haptic_preset', {
+    pad: pad,
+    name: name,
+  });
+}
+
+/**
+ * @param name pattern name, e.g. 'modifier_lock' or 'long_press'
+ * @param queue play after currently playing pattern instead of replacing it
+ */
+async function playHapticPattern(name: string, queue: boolean = false) {
+  return invoke('play_haptic_pattern', {
+    name: name,
+    queue: queue,
   });
 }
 
@@ -166,6 +182,14 @@ interface CursorConfig {
   forceThreshold: number;
   hapticOnHover: boolean;
   hapticOnClick: boolean;
+  /**
+   * vibrate when a layer key is pressed or a gesture switches layers
+   */
+  hapticOnLayerSwitch: boolean;
+  /**
+   * physical button which presses the key below the gyro pointer
+   */
+  gyroClickButton: string;
   area: {
     left: {
       top: number;
@@ -178,13 +202,27 @@ interface CursorConfig {
       left: number;
       width: number;
       height: number;
+    },
+    gyro: {
+      top: number;
+      left: number;
+      width: number;
+      height: number;
     }
   }
 };
 
+/**
+ * Physical controller button name (e.g. 'a', 'l4' or 'dpad_up') => key or layer.
+ */
+interface ButtonsConfig {
+  [button: string]: KeyboardKeyOptionKey | KeyboardKeyOptionLayer;
+}
+
 interface Config {
   deadzone: number;
   cursor: CursorConfig;
+  buttons: ButtonsConfig;
   layers: {
     [key: string]: KeyboardLayout;
   };
@@ -196,6 +234,8 @@ const defaultConfig: Config = {
     forceThreshold: 2000,
     hapticOnHover: true,
     hapticOnClick: true,
+    hapticOnLayerSwitch: false,
+    gyroClickButton: 'r2',
     area: {
       left: {
         top: 0.4,
@@ -209,8 +249,15 @@ const defaultConfig: Config = {
         width: 0.7,
         height: 0.7
       },
+      gyro: {
+        top: 0,
+        left: 0,
+        width: 1,
+        height: 1
+      },
     },
   },
+  buttons: {},
   layers: {
     default: defaultKeyboardLayout,
   }
@@ -244,6 +291,8 @@ class KeyboardState {
   pressedLeftKeys: KeyboardKey[] = [];
   pressedRightKeys: KeyboardKey[] = [];
 
+  pressedGyroKeys: KeyboardKey[] = [];
+
   hoveredLeftKeys: KeyboardKey[] = [];
   hoveredRightKeys: KeyboardKey[] = [];
 
@@ -253,6 +302,7 @@ class KeyboardState {
   afterKeyStateChangeListener: KeyStateChangeListener[] = [];
 
   heldOsmKeys: Set<KeyboardKeyOsm> = new Set();
+  hapticOnLayerSwitch: boolean = false;
   keyboardLayers: { [key: string]: KeyboardLayer } = {};
 
   isOsmShifted(): boolean {
@@ -413,6 +463,13 @@ interface KeyboardKeyOptionLayer {
   layer: string;
 }
 
+/**
+ * Shows device status instead of pressing a key, e.g. battery level.
+ */
+interface KeyboardKeyOptionStatus {
+  status: 'battery';
+}
+
 /**
  * Transparent key/neither key nor layer change.
  * Show key behind or nothing
@@ -424,6 +481,7 @@ type KeyboardKeyOptions =
   KeyboardKeyOptionsGeneric & (
   KeyboardKeyOptionKey |
   KeyboardKeyOptionLayer |
+  KeyboardKeyOptionStatus |
   KeyboardKeyOptionTrans
 );
 
@@ -434,6 +492,7 @@ class KeyboardKey extends HTMLElement {
   keyboardLayer: KeyboardLayer;
   key?: string;
   layer?: string;
+  status?: string;
   label: string;
 
   constructor(
@@ -449,6 +508,10 @@ class KeyboardKey extends HTMLElement {
     if ('layer' in options) {
       this.layer = options.layer;
     }
+    if ('status' in options) {
+      this.status = options.status;
+      this.classList.add('status', options.status);
+    }
     this.label = options?.label ?? this.key ?? "";
     this.addEventListener('mousedown', this.onMouseDown.bind(this));
     this.addEventListener('mouseup', this.onMouseUp.bind(this));
@@ -464,7 +527,7 @@ class KeyboardKey extends HTMLElement {
     if (options?.id) {
       this.id = options.id;
     }
-    if (!this.key && !this.layer) {
+    if (!this.key && !this.layer && !this.status) {
       this.classList.add('transparent');
     }
   }
@@ -493,6 +556,13 @@ class KeyboardKey extends HTMLElement {
     this.innerHTML = '';
   }
 
+  setLabel(label: string) {
+    this.label = label;
+    if (this.button) {
+      this.button.innerText = label;
+    }
+  }
+
   isShift(): boolean {
     return this.key === 'shift';
   }
@@ -534,6 +604,9 @@ class KeyboardKey extends HTMLElement {
     }
     if (this.layer) {
       if (state === 'down') {
+        if (this.keyboardState.hapticOnLayerSwitch) {
+          triggerHapticPreset(2, 'layer_switch');
+        }
         this.keyboardState.enableLayer(this.layer);
       } else if (state === 'up') {
         this.keyboardState.disableLayer(this.layer);
@@ -603,6 +676,7 @@ class KeyboardKeyOsm extends KeyboardKey {
       this.state = OsmState.DOUBLE_HOLD;
       this.stateStartTime = now;
       log('debug', 'double hold now');
+      playHapticPattern('modifier_lock');
       this.keyboardState.holdOsmKey(this);
       return;
     }
@@ -639,7 +713,7 @@ if ('customElements' in window) {
 }
 
 function isKey(object: any) {
-  if ('key' in object || 'layer' in object) {
+  if ('key' in object || 'layer' in object || 'status' in object) {
     return true;
   }
   return !('elements' in object);
@@ -823,10 +897,10 @@ function handleHoveredKeys(
   });
   // if new keys are hovered/cursor moved to new key, send haptic feedback
   if (newHoveredLeftKeys.length != 0 && config?.cursor?.hapticOnHover) {
-    triggerHapticPulse(1);
+    triggerHapticPreset(1, 'hover');
   }
   if (newHoveredRightKeys.length != 0 && config?.cursor?.hapticOnHover) {
-    triggerHapticPulse(0);
+    triggerHapticPreset(0, 'hover');
   }
   keyboardState.hoveredLeftKeys = leftKeys;
   keyboardState.hoveredRightKeys = rightKeys;
@@ -858,7 +932,53 @@ interface SteamDeckDeviceReport {
     rPadX: number;
     rPadY: number;
     rPadForce: number;
-    l4: boolean;
+    lStickX: number;
+    lStickY: number;
+    rStickX: number;
+    rStickY: number;
+    lTrigger: number;
+    rTrigger: number;
+    /**
+     * button name => pressed, e.g. { a: true, l4: false, quick_access: false }
+     */
+    buttons: { [button: string]: boolean };
+    accelX: number;
+    accelY: number;
+    accelZ: number;
+    gyroPitch: number;
+    gyroYaw: number;
+    gyroRoll: number;
+    orientationW: number;
+    orientationX: number;
+    orientationY: number;
+    orientationZ: number;
+    /**
+     * s16 like touchpad coordinates, only set if gyro pointer is active
+     */
+    gyroPointerX: number;
+    gyroPointerY: number;
+    gyroPointerActive: boolean;
+    /** name of the plugin which sent the input, e.g. steamdeck or evdev */
+    source: string;
+}
+
+/**
+ * Payload of the device-connected and device-disconnected events.
+ */
+interface DeviceEvent {
+  /** name of the plugin, e.g. steamdeck or evdev */
+  source: string;
+  /** name of the device */
+  name: string;
+}
+
+interface DeviceStatus {
+  /** in percent */
+  batteryLevel: number | null;
+  voltageMv: number | null;
+  charging: boolean | null;
+  /** name of the plugin which sent the status */
+  source: string;
 }
 
 /**
@@ -932,7 +1052,7 @@ async function handleTouchpads(
   if (lastInput.lPadForce < config.cursor.forceThreshold
       && input.lPadForce > config.cursor.forceThreshold) {
     if (config?.cursor?.hapticOnClick) {
-      triggerHapticPulse(1);
+      triggerHapticPreset(1, 'key_press');
     }
     keyboardState.keyStateChanges(
       leftKeys, 'down', now);
@@ -940,7 +1060,7 @@ async function handleTouchpads(
   } else if (lastInput.lPadForce > config.cursor.forceThreshold
       && input.lPadForce < config.cursor.forceThreshold) {
     if (config?.cursor?.hapticOnClick) {
-      triggerHapticPulse(1);
+      triggerHapticPreset(1, 'key_press');
     }
     keyboardState.keyStateChanges(
       keyboardState.pressedLeftKeys, 'up', now);
@@ -949,7 +1069,7 @@ async function handleTouchpads(
   if (lastInput.rPadForce < config.cursor.forceThreshold
       && input.rPadForce > config.cursor.forceThreshold) {
     if (config?.cursor?.hapticOnClick) {
-      triggerHapticPulse(0);
+      triggerHapticPreset(0, 'key_press');
     }
     keyboardState.keyStateChanges(
       rightKeys, 'down', now);
@@ -957,7 +1077,7 @@ async function handleTouchpads(
   } else if (lastInput.rPadForce > config.cursor.forceThreshold
       && input.rPadForce < config.cursor.forceThreshold) {
     if (config?.cursor?.hapticOnClick) {
-      triggerHapticPulse(0);
+      triggerHapticPreset(0, 'key_press');
     }
     keyboardState.keyStateChanges(
       keyboardState.pressedRightKeys, 'up', now);
@@ -965,13 +1085,90 @@ async function handleTouchpads(
   }
 }
 
+/**
+ * Moves/hides/shows gyro cursor and presses keys below it with the gyro click button.
+ *
+ * @param config config information
+ * @param keyboardState information about current state of the keyboard
+ * @param input current input
+ * @param lastInput input from last frame/tick
+ * @param gyroCursor cursor for gyro pointer
+ */
+async function handleGyroPointer(
+    config: Config,
+    keyboardState: KeyboardState,
+    input: SteamDeckDeviceReport,
+    lastInput: SteamDeckDeviceReport,
+    gyroCursor: HTMLElement) {
+  if (!input.gyroPointerActive) {
+    gyroCursor.classList.add('hidden');
+    return;
+  }
+  gyroCursor.classList.remove('hidden');
+  const [gyroCursorX, gyroCursorY] = transform(
+    input.gyroPointerX,
+    input.gyroPointerY,
+    config.cursor.area.gyro ?? defaultConfig.cursor.area.gyro);
+  gyroCursor.style.top = (gyroCursorY - cursorSize / 2) + 'px';
+  gyroCursor.style.left = (gyroCursorX - cursorSize / 2) + 'px';
+  const clickButton = config.cursor.gyroClickButton
+    ?? defaultConfig.cursor.gyroClickButton;
+  const isPressed = input.buttons?.[clickButton] ?? false;
+  const wasPressed = lastInput.buttons?.[clickButton] ?? false;
+  const now = new Date();
+  if (isPressed && !wasPressed) {
+    const keys = getKeys(gyroCursorX, gyroCursorY);
+    keyboardState.keyStateChanges(keys, 'down', now);
+    keyboardState.pressedGyroKeys = keys;
+  } else if (!isPressed && wasPressed) {
+    keyboardState.keyStateChanges(
+      keyboardState.pressedGyroKeys, 'up', now);
+    keyboardState.pressedGyroKeys = [];
+  }
+}
+
+/**
+ * Sends key or enables/disables layer for pressed/released physical buttons.
+ *
+ * @param config config information
+ * @param keyboardState information about current state of the keyboard
+ * @param input current input
+ * @param lastInput input from last frame/tick
+ */
+async function handleButtons(
+    config: Config,
+    keyboardState: KeyboardState,
+    input: SteamDeckDeviceReport,
+    lastInput: SteamDeckDeviceReport) {
+  for (const [button, binding] of Object.entries(config?.buttons ?? {})) {
+    const isPressed = input.buttons?.[button] ?? false;
+    const wasPressed = lastInput.buttons?.[button] ?? false;
+    if (isPressed == wasPressed) {
+      continue;
+    }
+    const state: KeyState = isPressed ? 'down' : 'up';
+    log('debug', `Button ${button} ${state}`);
+    if ('key' in binding) {
+      sendKey(binding.key, state);
+    } else if ('layer' in binding) {
+      if (isPressed) {
+        keyboardState.enableLayer(binding.layer);
+      } else {
+        keyboardState.disableLayer(binding.layer);
+      }
+    }
+  }
+}
+
 class App {
 
   config: Config;
   keyboardState: KeyboardState;
-  lastInput: SteamDeckDeviceReport | undefined;
+  lastInputs: Map<string, SteamDeckDeviceReport> = new Map();
+  deviceStatus?: DeviceStatus;
   leftCursor: HTMLElement;
   rightCursor: HTMLElement;
+  gyroCursor: HTMLElement;
 
   constructor() {
     this.config = {
@@ -980,21 +1177,88 @@ class App {
     this.keyboardState = new KeyboardState();
     const leftCursor = document.querySelector<HTMLElement>('#leftCursor');
     const rightCursor = document.querySelector<HTMLElement>('#rightCursor');
+    const gyroCursor = document.querySelector<HTMLElement>('#gyroCursor');
     if (!leftCursor) {
       throw Error('Left cursor html element missing');
     }
     if (!rightCursor) {
       throw Error('Right cursor html element missing');
     }
+    if (!gyroCursor) {
+      throw Error('Gyro cursor html element missing');
+    }
     this.leftCursor = leftCursor;
     this.rightCursor = rightCursor;
+    this.gyroCursor = gyroCursor;
   }
 
   async initListener() {
     await listen('config', this.onConfig.bind(this));
+    await listen('switch-layer', this.onSwitchLayer.bind(this));
+    await listen('device-connected', this.onDeviceConnected.bind(this));
+    await listen('device-disconnected', this.onDeviceDisconnected.bind(this));
+    await listen('device-status', this.onDeviceStatus.bind(this));
     await readConfig();
   }
 
+  async onDeviceConnected(event: { payload: DeviceEvent }) {
+    const device = event.payload;
+    log('info', `Input device of ${device.source} connected: ${device.name}`);
+  }
+
+  /**
+   * Hides cursors, as there won't be any input to hide them until reconnected.
+   */
+  async onDeviceDisconnected(event: { payload: DeviceEvent }) {
+    const device = event.payload;
+    log('warn', `Input device of ${device.source} disconnected: ${device.name}`);
+    this.leftCursor.classList.add('hidden');
+    this.rightCursor.classList.add('hidden');
+    this.gyroCursor.classList.add('hidden');
+    this.lastInputs.delete(device.source);
+  }
+
+  /**
+   * Shows battery level on status keys, e.g. `{ "status": "battery" }` in the top row.
+   */
+  async onDeviceStatus(event: { payload: DeviceStatus }) {
+    const status = event.payload;
+    log('debug', `Device status of ${status.source}: ${JSON.stringify(status)}`);
+    if (status.batteryLevel === null) {
+      return;
+    }
+    this.deviceStatus = status;
+    this.showDeviceStatus();
+  }
+
+  showDeviceStatus() {
+    const status = this.deviceStatus;
+    if (!status) {
+      return;
+    }
+    document.body.dataset.batteryLevel = `${status.batteryLevel}`;
+    document.body.dataset.charging = `${status.charging ?? false}`;
+    document.querySelectorAll<KeyboardKey>('.key.status.battery').forEach(key => {
+      key.setLabel(`${status.batteryLevel}%`);
+      key.classList.toggle('charging', status.charging ?? false);
+    });
+  }
+
+  /**
+   * Enables layer or disables it if already active, e.g. from a gesture.
+   */
+  async onSwitchLayer(event: { payload: string }) {
+    const layerName = event.payload;
+    if (this.config?.cursor?.hapticOnLayerSwitch) {
+      triggerHapticPreset(2, 'layer_switch');
+    }
+    if (this.keyboardState.keyboardLayers[layerName]?.active) {
+      this.keyboardState.disableLayer(layerName);
+    } else {
+      this.keyboardState.enableLayer(layerName);
+    }
+  }
+
   async onConfig(event: { payload: string }) {
     this.config = {
       ...this.config,
@@ -1002,6 +1266,8 @@ class App {
     }
     const body = document.querySelector('body');
     this.keyboardState = new KeyboardState();
+    this.keyboardState.hapticOnLayerSwitch =
+      this.config.cursor.hapticOnLayerSwitch ?? false;
     const renderedKeyboardLayers = renderKeyboardLayoutLayers(
       this.keyboardState, this.config.layers);
     // add rendered keyboard layout to DOM
@@ -1016,6 +1282,7 @@ class App {
     // enable first layer
     const firstLayerName = Object.keys(renderedKeyboardLayers)[0];
     this.keyboardState.enableLayer(firstLayerName); 
+    this.showDeviceStatus();
     await listen('input', this.onInput.bind(this));
   }
 
@@ -1024,18 +1291,32 @@ class App {
       return;
     }
     let input = event.payload;
-    if (!this.lastInput) {
-      this.lastInput = input;
+    // compare with the last input of the same device, as several plugins may run at once
+    const lastInput = this.lastInputs.get(input.source);
+    this.lastInputs.set(input.source, input);
+    // lets layouts style by device, e.g. body[data-input-source="evdev"]
+    document.body.dataset.inputSource = input.source;
+    if (!lastInput) {
       return;
     }
     handleTouchpads(
       this.config,
       this.keyboardState,
       input,
-      this.lastInput,
+      lastInput,
       this.leftCursor,
       this.rightCursor);
-    this.lastInput = input;
+    handleGyroPointer(
+      this.config,
+      this.keyboardState,
+      input,
+      lastInput,
+      this.gyroCursor);
+    handleButtons(
+      this.config,
+      this.keyboardState,
+      input,
+      lastInput);
   }
 }
 